@@ -1,3 +1,4 @@
+use std::path::Path;
 use std::process::Command;
 
 fn main() {
@@ -26,4 +27,113 @@ fn main() {
 
     // Re-run if HEAD changes
     println!("cargo:rerun-if-changed=.git/HEAD");
+
+    embed_migrations();
+}
+
+/// Generate the lists of embedded migrations from src/migrations
+///
+/// Each SQL dialect has its own directory (`sqlite`, `postgres`) holding the
+/// same migrations. Files are named `<version>_<name>.sql`; the version is a
+/// timestamp and determines the order migrations are applied in. An optional
+/// `<version>_<name>.down.sql` reverts the migration.
+fn embed_migrations() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/migrations");
+
+    let sqlite = dialect_migrations(&dir.join("sqlite"));
+    let postgres = dialect_migrations(&dir.join("postgres"));
+
+    // Both dialects must describe the same schema history
+    let names = |ms: &[(i64, String, String, Option<String>)]| {
+        ms.iter()
+            .map(|(version, name, _, _)| (*version, name.clone()))
+            .collect::<Vec<_>>()
+    };
+    if names(&sqlite) != names(&postgres) {
+        panic!("src/migrations/sqlite and src/migrations/postgres have different migrations");
+    }
+
+    let out = Path::new(&std::env::var("OUT_DIR").unwrap()).join("migrations.rs");
+    std::fs::write(
+        out,
+        format!(
+            "{}{}",
+            migrations_static("SQLITE_MIGRATIONS", &sqlite),
+            migrations_static("POSTGRES_MIGRATIONS", &postgres)
+        ),
+    )
+    .unwrap();
+}
+
+/// Read one dialect's migrations as (version, name, up path, down path),
+/// sorted by version
+fn dialect_migrations(dir: &Path) -> Vec<(i64, String, String, Option<String>)> {
+    println!("cargo:rerun-if-changed={}", dir.display());
+
+    let mut ups: Vec<(i64, String, String)> = Vec::new();
+    let mut downs: Vec<(i64, String)> = Vec::new();
+
+    for entry in std::fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|ext| ext != "sql") {
+            continue;
+        }
+
+        let stem = path.file_stem().unwrap().to_str().unwrap().to_string();
+        let (stem, is_down) = match stem.strip_suffix(".down") {
+            Some(stem) => (stem.to_string(), true),
+            None => (stem, false),
+        };
+        let (version, name) = stem
+            .split_once('_')
+            .unwrap_or_else(|| panic!("Migration {stem} must be named <version>_<name>.sql"));
+        let version: i64 = version
+            .parse()
+            .unwrap_or_else(|_| panic!("Migration {stem} has a non-numeric version"));
+
+        if is_down {
+            downs.push((version, path.display().to_string()));
+        } else {
+            ups.push((version, name.to_string(), path.display().to_string()));
+        }
+    }
+
+    ups.sort_by_key(|(version, _, _)| *version);
+
+    if let Some(w) = ups.windows(2).find(|w| w[0].0 == w[1].0) {
+        panic!("Duplicate migration version {}", w[0].0);
+    }
+    if let Some((version, _)) = downs
+        .iter()
+        .find(|(v, _)| !ups.iter().any(|(u, _, _)| u == v))
+    {
+        panic!("Down migration {version} has no matching up migration");
+    }
+
+    ups.into_iter()
+        .map(|(version, name, path)| {
+            let down = downs
+                .iter()
+                .find(|(v, _)| *v == version)
+                .map(|(_, path)| path.clone());
+            (version, name, path, down)
+        })
+        .collect()
+}
+
+fn migrations_static(name: &str, migrations: &[(i64, String, String, Option<String>)]) -> String {
+    let entries: String = migrations
+        .iter()
+        .map(|(version, name, path, down)| {
+            let down = match down {
+                Some(path) => format!("Some(include_str!({path:?}))"),
+                None => "None".to_string(),
+            };
+            format!(
+                "    Migration {{ version: {version}, name: {name:?}, sql: include_str!({path:?}), down: {down} }},\n"
+            )
+        })
+        .collect();
+
+    format!("static {name}: &[Migration] = &[\n{entries}];\n")
 }
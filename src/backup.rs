@@ -0,0 +1,219 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use tokio::sync::watch;
+use tracing::{error, info};
+
+use crate::db::Database;
+
+const DEFAULT_INTERVAL_HOURS: u64 = 24;
+const DEFAULT_KEEP: usize = 7;
+const SNAPSHOT_PREFIX: &str = "bot-";
+const SNAPSHOT_SUFFIX: &str = ".db";
+/// Added to a snapshot's name until it's completely written
+const PARTIAL_SUFFIX: &str = ".partial";
+
+/// Where and how often scheduled snapshots are written
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BackupConfig {
+    /// Directory for snapshots; None disables scheduled snapshots
+    pub dir: Option<PathBuf>,
+    pub interval_hours: u64,
+    /// How many snapshots to keep; older ones are deleted
+    pub keep: usize,
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        Self {
+            dir: None,
+            interval_hours: DEFAULT_INTERVAL_HOURS,
+            keep: DEFAULT_KEEP,
+        }
+    }
+}
+
+/// Write a timestamped snapshot to `dir`, returning its path
+async fn snapshot(db: &Database, dir: &Path) -> Result<PathBuf> {
+    tokio::fs::create_dir_all(dir)
+        .await
+        .with_context(|| format!("Failed to create {}", dir.display()))?;
+
+    let name = format!(
+        "{SNAPSHOT_PREFIX}{}{SNAPSHOT_SUFFIX}",
+        chrono::Utc::now().format("%Y%m%d-%H%M%S")
+    );
+    let path = dir.join(name);
+
+    // Written under another name first, so an interrupted snapshot is never
+    // mistaken for a complete one
+    let partial = dir.join(format!("{}{PARTIAL_SUFFIX}", path.display()));
+    db.backup(&partial).await?;
+    tokio::fs::rename(&partial, &path)
+        .await
+        .with_context(|| format!("Failed to rename {}", partial.display()))?;
+
+    Ok(path)
+}
+
+/// Delete all but the newest `keep` snapshots in `dir`, and any left
+/// unfinished
+async fn rotate(dir: &Path, keep: usize) -> Result<()> {
+    let mut snapshots = Vec::new();
+    let mut entries = tokio::fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !name.starts_with(SNAPSHOT_PREFIX) {
+            continue;
+        }
+        if name.ends_with(SNAPSHOT_SUFFIX) {
+            snapshots.push(entry.path());
+        } else if name.ends_with(&format!("{SNAPSHOT_SUFFIX}{PARTIAL_SUFFIX}")) {
+            tokio::fs::remove_file(entry.path())
+                .await
+                .with_context(|| {
+                    format!(
+                        "Failed to delete unfinished snapshot {}",
+                        entry.path().display()
+                    )
+                })?;
+            info!("Deleted unfinished snapshot {}", entry.path().display());
+        }
+    }
+
+    // Timestamped names sort oldest first
+    snapshots.sort();
+    let expired = snapshots.len().saturating_sub(keep);
+    for path in &snapshots[..expired] {
+        tokio::fs::remove_file(path)
+            .await
+            .with_context(|| format!("Failed to delete old snapshot {}", path.display()))?;
+        info!("Deleted old snapshot {}", path.display());
+    }
+
+    Ok(())
+}
+
+/// Snapshot the database now and then on every interval until `shutdown`
+/// changes, if a snapshot directory is configured
+///
+/// A snapshot in progress is finished before stopping.
+pub async fn run(db: Database, config: BackupConfig, mut shutdown: watch::Receiver<bool>) {
+    let Some(dir) = config.dir else {
+        return;
+    };
+    let every = Duration::from_secs(config.interval_hours * 3600);
+    info!(
+        "Database snapshots to {} every {:?}, keeping {}",
+        dir.display(),
+        every,
+        config.keep
+    );
+
+    let mut interval = tokio::time::interval(every);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = shutdown.changed() => break,
+        }
+
+        match snapshot(&db, &dir).await {
+            Ok(path) => {
+                info!("Wrote database snapshot {}", path.display());
+                if let Err(e) = rotate(&dir, config.keep).await {
+                    error!("Failed to rotate database snapshots: {:#}", e);
+                }
+            }
+            Err(e) => error!("Database snapshot failed: {:#}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::DatabaseConfig;
+
+    /// A new directory under the system temp directory
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("bot_test_{}_{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[tokio::test]
+    async fn rotate_keeps_the_newest_and_drops_unfinished() {
+        let dir = temp_dir("rotate");
+        for name in [
+            "bot-20260101-000000.db",
+            "bot-20260102-000000.db",
+            "bot-20260103-000000.db",
+            "bot-20260104-000000.db.partial",
+            "notes.txt",
+        ] {
+            std::fs::write(dir.join(name), b"").unwrap();
+        }
+
+        rotate(&dir, 2).await.unwrap();
+        assert_eq!(
+            names(&dir),
+            [
+                "bot-20260102-000000.db",
+                "bot-20260103-000000.db",
+                "notes.txt"
+            ]
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn run_stops_on_shutdown() {
+        let dir = temp_dir("run");
+        let db = Database::init(&DatabaseConfig {
+            url: format!("sqlite:{}", dir.join("source.db").display()),
+            ..DatabaseConfig::default()
+        })
+        .await
+        .unwrap();
+        let snapshots = dir.join("snapshots");
+        let config = BackupConfig {
+            dir: Some(snapshots.clone()),
+            ..BackupConfig::default()
+        };
+
+        let (shutdown, receiver) = watch::channel(false);
+        let task = tokio::spawn(run(db, config, receiver));
+        // The first snapshot is taken straight away
+        while !snapshots.exists() || !names(&snapshots).iter().any(|n| n.ends_with(".db")) {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        shutdown.send_replace(true);
+        tokio::time::timeout(Duration::from_secs(5), task)
+            .await
+            .expect("backups didn't stop")
+            .unwrap();
+
+        let written = names(&snapshots);
+        assert_eq!(written.len(), 1);
+        assert!(written[0].ends_with(SNAPSHOT_SUFFIX));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
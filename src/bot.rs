@@ -1,80 +1,478 @@
-use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
 use serenity::async_trait;
 use serenity::builder::{
-    CreateCommand, CreateCommandOption, CreateInteractionResponse, CreateInteractionResponseMessage,
+    CreateAttachment, CreateCommand, CreateCommandOption, CreateEmbed, CreateInteractionResponse,
+    CreateInteractionResponseMessage, CreateMessage, EditInteractionResponse,
+};
+use serenity::model::application::{
+    CommandDataOptionValue, CommandInteraction, CommandOptionType, Interaction,
 };
-use serenity::model::application::{CommandOptionType, Interaction};
 use serenity::model::prelude::*;
 use serenity::prelude::*;
 use tracing::{debug, error, info};
 
-use crate::db::{CommandLog, Database};
-
-#[derive(Debug, Deserialize)]
-#[allow(dead_code)]
-struct PlayerInfo {
-    count: u32,
-    updated_at: String,
-    age: String,
+use crate::commands::{self, CommandOutcome};
+use crate::db::{CommandLog, GuildSettings, ReplyStyle, ServerQuery};
+use crate::discord::{download_attachment, is_pcap_file};
+use crate::servers::{fetch_servers, find_server};
+use crate::settings::SettingsCache;
+use crate::store::{CommandLogStore, ServerStore, SettingsStore};
+use crate::{metrics, pcap, protocol, scrub};
+
+/// Number of message types listed in a capture summary
+const SUMMARY_TOP_MESSAGES: usize = 3;
+
+/// Format a reply as plain text or an embed
+fn styled_reply(style: ReplyStyle, content: String) -> CreateInteractionResponseMessage {
+    match style {
+        ReplyStyle::Text => CreateInteractionResponseMessage::new().content(content),
+        ReplyStyle::Embed => {
+            CreateInteractionResponseMessage::new().embed(CreateEmbed::new().description(content))
+        }
+    }
 }
 
-#[derive(Debug, Deserialize)]
-#[allow(dead_code)]
-struct ServerInfo {
-    name: String,
-    description: String,
-    #[serde(rename = "type")]
-    server_type: String,
-    software: String,
-    host: String,
-    port: String,
-    website_url: Option<String>,
-    discord_url: Option<String>,
-    players: Option<PlayerInfo>,
+/// Format a message as plain text or an embed
+fn styled_message(style: ReplyStyle, content: String) -> CreateMessage {
+    match style {
+        ReplyStyle::Text => CreateMessage::new().content(content),
+        ReplyStyle::Embed => CreateMessage::new().embed(CreateEmbed::new().description(content)),
+    }
 }
 
-async fn fetch_servers() -> Result<Vec<ServerInfo>, String> {
-    let response = reqwest::get("https://treestats.net/servers.json")
-        .await
-        .map_err(|e| format!("Failed to fetch servers: {}", e))?;
-
-    let servers: Vec<ServerInfo> = response
-        .json()
-        .await
-        .map_err(|e| format!("Failed to parse servers: {}", e))?;
+/// Describe what's in a capture in a sentence or two
+fn capture_summary(data: &[u8]) -> Option<String> {
+    let capture = pcap::parse(data).ok()?;
+    let packets = protocol::decode(&capture);
+
+    let duration_us = match (packets.first(), packets.last()) {
+        (Some(first), Some(last)) => last.timestamp_us - first.timestamp_us,
+        _ => 0,
+    };
+    let game_packets = packets.iter().filter(|p| p.sequence.is_some()).count();
+
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for message in packets.iter().flat_map(|p| &p.messages) {
+        let name = match message.name {
+            Some(name) => name.to_string(),
+            None => format!("0x{:04X}", message.opcode),
+        };
+        *counts.entry(name).or_default() += 1;
+    }
+    let mut counts: Vec<(String, usize)> = counts.into_iter().collect();
+    counts.sort_by(|(a_name, a), (b_name, b)| b.cmp(a).then(a_name.cmp(b_name)));
+
+    let mut summary = format!(
+        "{} packet{} over {:.1}s, {} of them game traffic.",
+        packets.len(),
+        if packets.len() == 1 { "" } else { "s" },
+        duration_us as f64 / 1_000_000.0,
+        game_packets
+    );
+    if !counts.is_empty() {
+        let top = counts
+            .iter()
+            .take(SUMMARY_TOP_MESSAGES)
+            .map(|(name, count)| format!("`{name}` ×{count}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        summary.push_str(&format!(" Most common messages: {top}."));
+    }
 
-    Ok(servers)
+    Some(summary)
 }
 
-fn find_server<'a>(servers: &'a [ServerInfo], query: &str) -> Option<&'a ServerInfo> {
-    // First try exact case-insensitive match
-    if let Some(server) = servers.iter().find(|s| s.name.eq_ignore_ascii_case(query)) {
-        return Some(server);
-    }
+/// Build the `/server` reply for a server name query, recording which server
+/// it resolved to
+async fn server_response(
+    store: &dyn ServerStore,
+    guild_id: Option<GuildId>,
+    server_name: &str,
+) -> (String, CommandOutcome) {
+    match fetch_servers().await {
+        Ok(servers) => {
+            let aliases = match store
+                .get_aliases(guild_id.map(|id| id.to_string()).as_deref())
+                .await
+            {
+                Ok(aliases) => aliases,
+                Err(e) => {
+                    error!("Failed to fetch aliases: {}", e);
+                    Vec::new()
+                }
+            };
+            let found = find_server(&servers, &aliases, server_name);
+
+            // Keep every lookup so misses can be reviewed with /stats queries
+            let query = ServerQuery {
+                query: server_name.to_string(),
+                resolved_name: found.server.map(|s| s.name.clone()),
+                closest_name: found.closest.map(|(s, _)| s.name.clone()),
+                score: found.closest.map(|(_, score)| score),
+                guild_id: guild_id.map(|id| id.to_string()),
+            };
+            if let Err(e) = store.log_server_query(query).await {
+                error!("Failed to log server query: {}", e);
+            }
 
-    // Fall back to fuzzy matching with a threshold
-    // Require query to be at least 50% of the server name length to avoid very short queries matching
-    const SIMILARITY_THRESHOLD: f64 = 0.8;
-    const MIN_QUERY_LENGTH_RATIO: f64 = 0.5;
-
-    servers
-        .iter()
-        .filter(|s| {
-            let min_length = (s.name.len() as f64 * MIN_QUERY_LENGTH_RATIO).ceil() as usize;
-            query.len() >= min_length
-        })
-        .map(|s| {
-            let similarity = strsim::jaro_winkler(&s.name.to_lowercase(), &query.to_lowercase());
-            (s, similarity)
-        })
-        .filter(|(_, similarity)| *similarity >= SIMILARITY_THRESHOLD)
-        .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
-        .map(|(server, _)| server)
+            if let Some(server) = found.server {
+                let mut response = format!(
+                    "You can connect to {} at `{}:{}`.",
+                    server.name, server.host, server.port
+                );
+
+                match (&server.discord_url, &server.players) {
+                    (Some(discord_url), Some(players)) => {
+                        response.push_str(&format!(
+                            " {}'s Discord is {}. As of {}, {} character{} {} in the game world.",
+                            server.name,
+                            discord_url,
+                            players.age,
+                            players.count,
+                            if players.count == 1 { "" } else { "s" },
+                            if players.count == 1 { "was" } else { "were" }
+                        ));
+                    }
+                    (None, Some(players)) => {
+                        response.push_str(&format!(
+                            " {} doesn't have a Discord. As of {}, {} character{} {} in the game world.",
+                            server.name,
+                            players.age,
+                            players.count,
+                            if players.count == 1 { "" } else { "s" },
+                            if players.count == 1 { "was" } else { "were" }
+                        ));
+                    }
+                    (Some(discord_url), None) => {
+                        response.push_str(&format!(
+                            " {}'s Discord is {}. I don't seem to have any information on player counts. They must not use TreeStats :(",
+                            server.name,
+                            discord_url
+                        ));
+                    }
+                    (None, None) => {
+                        response.push_str(&format!(
+                            " {} doesn't have a Discord and I don't seem to have any information on player counts. They must not use TreeStats :(",
+                            server.name
+                        ));
+                    }
+                }
+
+                let outcome = CommandOutcome {
+                    detail: Some(server.name.clone()),
+                    ..Default::default()
+                };
+                (response, outcome)
+            } else {
+                let response = format!(
+                    "Server '{}' not found. Please check the name and try again.",
+                    server_name
+                );
+                (response, CommandOutcome::default())
+            }
+        }
+        Err(e) => {
+            error!("Failed to fetch servers: {}", e);
+            (
+                "Failed to fetch server list. Please try again later.".to_string(),
+                CommandOutcome::error(e),
+            )
+        }
+    }
 }
 
 pub struct Handler {
     pub web_url: String,
-    pub db: Database,
+    /// Where command logs go
+    pub store: Arc<dyn CommandLogStore>,
+    /// Aliases and `/server` lookups
+    pub servers: Arc<dyn ServerStore>,
+    pub settings: SettingsCache,
+}
+
+impl Handler {
+    pub fn new(
+        web_url: String,
+        store: Arc<dyn CommandLogStore>,
+        servers: Arc<dyn ServerStore>,
+        settings: Arc<dyn SettingsStore>,
+    ) -> Self {
+        Self {
+            web_url,
+            store,
+            servers,
+            settings: SettingsCache::new(settings),
+        }
+    }
+
+    /// Dispatch a slash command and send its response
+    async fn run_command(&self, ctx: &Context, command: &CommandInteraction) -> CommandOutcome {
+        let (data, mut outcome) = match command.data.name.as_str() {
+            // Sends its own (deferred) response
+            "scrub" => return self.scrub_command(ctx, command).await,
+            "alias" => commands::alias::run(ctx, self.servers.as_ref(), command).await,
+            "privacy" => commands::privacy::run(ctx, self.store.as_ref(), command).await,
+            "config" => {
+                commands::config::run(ctx, self.servers.as_ref(), &self.settings, command).await
+            }
+            _ => self.respond(command).await,
+        };
+
+        let builder = CreateInteractionResponse::Message(data);
+
+        if let Err(e) = command.create_response(&ctx.http, builder).await {
+            error!("Failed to respond to command: {}", e);
+            outcome
+                .error
+                .get_or_insert_with(|| format!("Failed to respond: {e}"));
+        }
+
+        outcome
+    }
+
+    /// Build the response to a command that doesn't need to call Discord
+    async fn respond(
+        &self,
+        command: &CommandInteraction,
+    ) -> (CreateInteractionResponseMessage, CommandOutcome) {
+        match command.data.name.as_str() {
+            "status" => (
+                CreateInteractionResponseMessage::new().content("Okay"),
+                CommandOutcome::default(),
+            ),
+            "server" => {
+                let settings = self.settings.get(command.guild_id).await;
+                let server_name = command
+                    .data
+                    .options
+                    .iter()
+                    .find(|opt| opt.name == "name")
+                    .and_then(|opt| opt.value.as_str())
+                    .or(settings.default_server.as_deref());
+
+                match server_name {
+                    Some(server_name) => {
+                        let (content, outcome) =
+                            server_response(self.servers.as_ref(), command.guild_id, server_name)
+                                .await;
+                        (styled_reply(settings.reply_style, content), outcome)
+                    }
+                    None => (
+                        CreateInteractionResponseMessage::new()
+                            .content("Please give a server name. Admins can set a default with `/config default-server`.")
+                            .ephemeral(true),
+                        CommandOutcome::error("Missing server name"),
+                    ),
+                }
+            }
+            "stats" => {
+                commands::stats::run(self.store.as_ref(), self.servers.as_ref(), command).await
+            }
+            _ => (
+                CreateInteractionResponseMessage::new().content("Unknown command"),
+                CommandOutcome::error("Unknown command"),
+            ),
+        }
+    }
+
+    /// Count a finished slash command and record it in the command log
+    async fn log_interaction(
+        &self,
+        command: &CommandInteraction,
+        outcome: CommandOutcome,
+        latency: Duration,
+    ) {
+        metrics::SLASH_COMMANDS
+            .with_label_values(&[
+                command.data.name.as_str(),
+                if outcome.error.is_none() {
+                    "success"
+                } else {
+                    "error"
+                },
+            ])
+            .inc();
+
+        let log = CommandLog {
+            command_name: command.data.name.clone(),
+            user_id: command.user.id.to_string(),
+            user_name: command.user.name.clone(),
+            channel_id: command.channel_id.to_string(),
+            guild_id: command.guild_id.map(|id| id.to_string()),
+            message_id: Some(command.id.to_string()),
+            success: outcome.error.is_none(),
+            error_message: outcome.error,
+            options: serde_json::to_string(&command.data.options).ok(),
+            latency_ms: Some(latency.as_millis() as i64),
+            detail: outcome.detail,
+        };
+
+        if let Err(e) = self.store.log_command(log).await {
+            error!("Failed to log command to database: {}", e);
+        }
+    }
+
+    /// The settings to reply to a capture posted in a channel with, or None
+    /// if detection is turned off there
+    async fn detection_settings(
+        &self,
+        guild_id: Option<GuildId>,
+        channel_id: ChannelId,
+    ) -> Option<GuildSettings> {
+        let settings = self.settings.get(guild_id).await;
+        let enabled = match guild_id {
+            Some(_) => settings.pcap_detection_enabled(&channel_id.to_string()),
+            None => self.settings.pcap_in_dms().await,
+        };
+        enabled.then_some(settings)
+    }
+
+    /// Let the poster know if their capture includes their login details
+    ///
+    /// We prefer a DM so the account name isn't repeated in public, and only
+    /// fall back to a (vaguer) reply if the user doesn't accept DMs.
+    async fn warn_about_credentials(
+        &self,
+        ctx: &Context,
+        msg: &Message,
+        attachment: &Attachment,
+        data: &[u8],
+    ) {
+        let login = match pcap::parse(data) {
+            Ok(capture) => protocol::find_credentials(&capture),
+            Err(e) => {
+                debug!(
+                    "Skipping credential check for {}: {}",
+                    attachment.filename, e
+                );
+                return;
+            }
+        };
+
+        let Some(login) = login else {
+            return;
+        };
+
+        info!(
+            "Capture {} in message {} contains a login request",
+            attachment.filename, msg.id
+        );
+
+        let secret = if login.has_secret {
+            " and your password or login ticket"
+        } else {
+            ""
+        };
+        let dm = format!(
+            "Heads up: the capture you posted in {} ({}) includes a login request containing your account name (`{}`){secret}. \
+             Anyone who downloads it can see them. I'd recommend deleting the message and re-uploading a scrubbed copy made with `/scrub`.",
+            msg.channel_id.mention(),
+            attachment.filename,
+            login.account,
+        );
+
+        if let Err(e) = msg
+            .author
+            .direct_message(&ctx.http, CreateMessage::new().content(dm))
+            .await
+        {
+            info!(
+                "Couldn't DM {} about credentials, replying instead: {}",
+                msg.author.id, e
+            );
+
+            let reply = "Heads up: this capture appears to include your account login details. \
+                         I'd recommend deleting it and re-uploading a copy scrubbed with `/scrub`.";
+            if let Err(e) = msg.reply(&ctx.http, reply).await {
+                error!("Failed to send credential warning: {}", e);
+            }
+        }
+    }
+
+    /// Handle `/scrub`: anonymize an uploaded capture and send it back
+    async fn scrub_command(&self, ctx: &Context, command: &CommandInteraction) -> CommandOutcome {
+        let attachment = command
+            .data
+            .options
+            .iter()
+            .find(|opt| opt.name == "capture")
+            .and_then(|opt| match opt.value {
+                CommandDataOptionValue::Attachment(id) => {
+                    command.data.resolved.attachments.get(&id)
+                }
+                _ => None,
+            });
+
+        let Some(attachment) = attachment.filter(|a| is_pcap_file(&a.filename)) else {
+            let data = CreateInteractionResponseMessage::new()
+                .content("Please attach a .pcap or .pcapng file to scrub.")
+                .ephemeral(true);
+            if let Err(e) = command
+                .create_response(&ctx.http, CreateInteractionResponse::Message(data))
+                .await
+            {
+                error!("Failed to respond to command: {}", e);
+            }
+            return CommandOutcome::error("Attachment is not a capture");
+        };
+
+        // Downloading and rewriting can take longer than Discord's 3s deadline
+        if let Err(e) = command.defer(&ctx.http).await {
+            error!("Failed to defer scrub command: {}", e);
+            return CommandOutcome::error(format!("Failed to defer: {e}"));
+        }
+
+        let mut outcome = CommandOutcome {
+            detail: Some(attachment.filename.clone()),
+            ..Default::default()
+        };
+
+        let response = match download_attachment(&attachment.url).await {
+            Ok(data) => match scrub::scrub(&data) {
+                Ok((scrubbed, report)) => {
+                    let filename = match attachment.filename.rsplit_once('.') {
+                        Some((stem, ext)) => format!("{stem}-scrubbed.{ext}"),
+                        None => format!("{}-scrubbed", attachment.filename),
+                    };
+
+                    EditInteractionResponse::new()
+                        .content(format!(
+                            "Here's your scrubbed capture. I remapped {} IP address{} and blanked {} login request{}.",
+                            report.addresses,
+                            if report.addresses == 1 { "" } else { "es" },
+                            report.logins,
+                            if report.logins == 1 { "" } else { "s" },
+                        ))
+                        .new_attachment(CreateAttachment::bytes(scrubbed, filename))
+                }
+                Err(e) => {
+                    info!("Failed to scrub {}: {}", attachment.filename, e);
+                    outcome.error = Some(format!("Failed to scrub: {e}"));
+                    EditInteractionResponse::new()
+                        .content(format!("I couldn't read that capture: {e}"))
+                }
+            },
+            Err((_, e)) => {
+                error!("Failed to download attachment for scrub: {}", e);
+                let response = EditInteractionResponse::new()
+                    .content(format!("Failed to download capture: {e}"));
+                outcome.error = Some(e);
+                response
+            }
+        };
+
+        if let Err(e) = command.edit_response(&ctx.http, response).await {
+            error!("Failed to respond to command: {}", e);
+            outcome
+                .error
+                .get_or_insert_with(|| format!("Failed to respond: {e}"));
+        }
+
+        outcome
+    }
 }
 
 #[async_trait]
@@ -88,11 +486,19 @@ impl EventHandler for Handler {
 
         let server_command = CreateCommand::new("server")
             .description("Get connection info for an AC server")
+            .add_option(CreateCommandOption::new(
+                CommandOptionType::String,
+                "name",
+                "Server name (supports fuzzy matching; defaults to this server's default)",
+            ));
+
+        let scrub_command = CreateCommand::new("scrub")
+            .description("Remove IP addresses and login details from a capture")
             .add_option(
                 CreateCommandOption::new(
-                    CommandOptionType::String,
-                    "name",
-                    "Server name (supports fuzzy matching)",
+                    CommandOptionType::Attachment,
+                    "capture",
+                    "The .pcap or .pcapng file to scrub",
                 )
                 .required(true),
             );
@@ -104,6 +510,38 @@ impl EventHandler for Handler {
         if let Err(e) = http.create_global_command(&server_command).await {
             error!("Failed to create server command: {}", e);
         }
+
+        if let Err(e) = http.create_global_command(&scrub_command).await {
+            error!("Failed to create scrub command: {}", e);
+        }
+
+        if let Err(e) = http
+            .create_global_command(&commands::stats::register())
+            .await
+        {
+            error!("Failed to create stats command: {}", e);
+        }
+
+        if let Err(e) = http
+            .create_global_command(&commands::alias::register())
+            .await
+        {
+            error!("Failed to create alias command: {}", e);
+        }
+
+        if let Err(e) = http
+            .create_global_command(&commands::config::register())
+            .await
+        {
+            error!("Failed to create config command: {}", e);
+        }
+
+        if let Err(e) = http
+            .create_global_command(&commands::privacy::register())
+            .await
+        {
+            error!("Failed to create privacy command: {}", e);
+        }
     }
 
     async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
@@ -113,85 +551,10 @@ impl EventHandler for Handler {
                 command.data.name, command.user.id
             );
 
-            let content = match command.data.name.as_str() {
-                "status" => "Okay".to_string(),
-                "server" => {
-                    let server_name = command
-                        .data
-                        .options
-                        .iter()
-                        .find(|opt| opt.name == "name")
-                        .and_then(|opt| opt.value.as_str())
-                        .unwrap_or("");
-
-                    match fetch_servers().await {
-                        Ok(servers) => {
-                            if let Some(server) = find_server(&servers, server_name) {
-                                let mut response = format!(
-                                    "You can connect to {} at `{}:{}`.",
-                                    server.name, server.host, server.port
-                                );
-
-                                match (&server.discord_url, &server.players) {
-                                    (Some(discord_url), Some(players)) => {
-                                        response.push_str(&format!(
-                                            " {}'s Discord is {}. As of {}, {} character{} {} in the game world.",
-                                            server.name,
-                                            discord_url,
-                                            players.age,
-                                            players.count,
-                                            if players.count == 1 { "" } else { "s" },
-                                            if players.count == 1 { "was" } else { "were" }
-                                        ));
-                                    }
-                                    (None, Some(players)) => {
-                                        response.push_str(&format!(
-                                            " {} doesn't have a Discord. As of {}, {} character{} {} in the game world.",
-                                            server.name,
-                                            players.age,
-                                            players.count,
-                                            if players.count == 1 { "" } else { "s" },
-                                            if players.count == 1 { "was" } else { "were" }
-                                        ));
-                                    }
-                                    (Some(discord_url), None) => {
-                                        response.push_str(&format!(
-                                            " {}'s Discord is {}. I don't seem to have any information on player counts. They must not use TreeStats :(",
-                                            server.name,
-                                            discord_url
-                                        ));
-                                    }
-                                    (None, None) => {
-                                        response.push_str(&format!(
-                                            " {} doesn't have a Discord and I don't seem to have any information on player counts. They must not use TreeStats :(",
-                                            server.name
-                                        ));
-                                    }
-                                }
-
-                                response
-                            } else {
-                                format!(
-                                    "Server '{}' not found. Please check the name and try again.",
-                                    server_name
-                                )
-                            }
-                        }
-                        Err(e) => {
-                            error!("Failed to fetch servers: {}", e);
-                            "Failed to fetch server list. Please try again later.".to_string()
-                        }
-                    }
-                }
-                _ => "Unknown command".to_string(),
-            };
-
-            let data = CreateInteractionResponseMessage::new().content(content);
-            let builder = CreateInteractionResponse::Message(data);
-
-            if let Err(e) = command.create_response(&ctx.http, builder).await {
-                error!("Failed to respond to command: {}", e);
-            }
+            let started = Instant::now();
+            let outcome = self.run_command(&ctx, &command).await;
+            self.log_interaction(&command, outcome, started.elapsed())
+                .await;
         }
     }
 
@@ -215,15 +578,41 @@ impl EventHandler for Handler {
             .find(|a| a.filename.to_lowercase().contains(".pcap"));
 
         if let Some(attachment) = pcap_attachment {
+            let Some(settings) = self.detection_settings(msg.guild_id, msg.channel_id).await else {
+                debug!(
+                    "Ignoring PCAP attachment in channel {} (detection disabled)",
+                    msg.channel_id
+                );
+                return;
+            };
+
+            let started = Instant::now();
+            metrics::PCAP_DETECTIONS.inc();
+
             info!(
                 "PCAP attachment detected: {} in channel {} message {}",
                 attachment.filename, msg.channel_id, msg.id
             );
 
+            // Downloaded once for both the summary and the credential check
+            let data = match download_attachment(&attachment.url).await {
+                Ok(data) => Some(data),
+                Err((_, e)) => {
+                    error!("Failed to download {}: {}", attachment.filename, e);
+                    None
+                }
+            };
+
             let web_link = format!("{}?channel={}&msg={}", self.web_url, msg.channel_id, msg.id);
-            let reply = format!("You can view your PCAP [here]({web_link})");
+            let mut reply = format!("You can view your PCAP [here]({web_link})");
+            if settings.auto_summarize
+                && let Some(summary) = data.as_deref().and_then(capture_summary)
+            {
+                reply.push_str(&format!("\n{summary}"));
+            }
+            let reply = styled_message(settings.reply_style, reply).reference_message(&msg);
 
-            let success = if let Err(e) = msg.reply(&ctx.http, reply).await {
+            let success = if let Err(e) = msg.channel_id.send_message(&ctx.http, reply).await {
                 error!("Failed to send reply: {}", e);
                 false
             } else {
@@ -237,39 +626,258 @@ impl EventHandler for Handler {
                 user_name: msg.author.name.clone(),
                 channel_id: msg.channel_id.to_string(),
                 guild_id: msg.guild_id.map(|id| id.to_string()),
-                message_id: msg.id.to_string(),
+                message_id: Some(msg.id.to_string()),
                 success,
                 error_message: if success {
                     None
                 } else {
                     Some("Failed to send reply".to_string())
                 },
+                options: None,
+                latency_ms: Some(started.elapsed().as_millis() as i64),
+                detail: Some(attachment.filename.clone()),
             };
 
-            if let Err(e) = self.db.log_command(log).await {
+            if let Err(e) = self.store.log_command(log).await {
                 error!("Failed to log command to database: {}", e);
             }
+
+            if let Some(data) = data {
+                self.warn_about_credentials(&ctx, &msg, attachment, &data)
+                    .await;
+            }
         }
     }
 }
 
-/// Start the Discord bot
-pub async fn start(
-    token: String,
-    web_url: String,
-    db: Database,
-) -> Result<(), Box<dyn std::error::Error>> {
-    info!("Starting bot with WEB_URL={}", web_url);
+/// Build the Discord client; `Client::start` connects to the gateway
+pub async fn client(token: String, handler: Handler) -> Result<Client, SerenityError> {
+    info!("Starting bot with WEB_URL={}", handler.web_url);
 
     let intents = GatewayIntents::GUILD_MESSAGES
         | GatewayIntents::DIRECT_MESSAGES
         | GatewayIntents::MESSAGE_CONTENT;
-    let handler = Handler { web_url, db };
-    let mut client = Client::builder(&token, intents)
+    Client::builder(&token, intents)
         .event_handler(handler)
-        .await?;
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{Value, json};
+
+    use super::*;
+    use crate::memory_store::MemoryStore;
+
+    const GUILD: u64 = 100;
+    const CHANNEL: u64 = 200;
+
+    fn handler(store: &Arc<MemoryStore>) -> Handler {
+        Handler::new(
+            "https://example.com".to_string(),
+            store.clone(),
+            store.clone(),
+            store.clone(),
+        )
+    }
+
+    /// A slash command invoked by user 1 in a guild, with Manage Server if
+    /// `admin`
+    fn command(name: &str, options: Value, admin: bool) -> CommandInteraction {
+        let user = json!({"id": "1", "username": "alice", "discriminator": "0", "avatar": null});
+        serde_json::from_value(json!({
+            "id": "300",
+            "application_id": "400",
+            "type": 2,
+            "data": {"id": "500", "name": name, "type": 1, "options": options},
+            "guild_id": GUILD.to_string(),
+            "channel_id": CHANNEL.to_string(),
+            "member": {
+                "user": user,
+                "roles": [],
+                "joined_at": "2024-01-01T00:00:00Z",
+                "deaf": false,
+                "mute": false,
+                "flags": 0,
+                "permissions": if admin { "32" } else { "0" },
+            },
+            "token": "token",
+            "version": 1,
+            "locale": "en-US",
+            "entitlements": [],
+        }))
+        .unwrap()
+    }
+
+    fn subcommand(name: &str) -> Value {
+        json!([{"name": name, "type": 1, "options": []}])
+    }
+
+    async fn respond(handler: &Handler, command: &CommandInteraction) -> (Value, CommandOutcome) {
+        let (data, outcome) = handler.respond(command).await;
+        (serde_json::to_value(data).unwrap(), outcome)
+    }
+
+    #[tokio::test]
+    async fn status() {
+        let store = Arc::new(MemoryStore::new());
+        let (response, outcome) =
+            respond(&handler(&store), &command("status", json!([]), false)).await;
+
+        assert_eq!(response["content"], "Okay");
+        assert!(outcome.error.is_none());
+    }
+
+    #[tokio::test]
+    async fn server_without_name_or_default() {
+        let store = Arc::new(MemoryStore::new());
+        let (response, outcome) =
+            respond(&handler(&store), &command("server", json!([]), false)).await;
+
+        assert!(
+            response["content"]
+                .as_str()
+                .unwrap()
+                .contains("/config default-server")
+        );
+        assert_eq!(outcome.error.as_deref(), Some("Missing server name"));
+    }
 
-    client.start().await?;
+    #[tokio::test]
+    async fn stats_global_counts_logged_commands() {
+        let store = Arc::new(MemoryStore::new());
+        let handler = handler(&store);
+
+        let status = command("status", json!([]), false);
+        for _ in 0..2 {
+            let outcome = handler.respond(&status).await.1;
+            handler
+                .log_interaction(&status, outcome, Duration::ZERO)
+                .await;
+        }
+        let unknown = command("nope", json!([]), false);
+        let outcome = handler.respond(&unknown).await.1;
+        handler
+            .log_interaction(&unknown, outcome, Duration::ZERO)
+            .await;
+
+        let (response, outcome) =
+            respond(&handler, &command("stats", subcommand("global"), false)).await;
+        assert_eq!(outcome.detail.as_deref(), Some("global"));
+        assert_eq!(
+            response["embeds"][0]["description"],
+            "**2** successful commands"
+        );
+        assert_eq!(response["embeds"][0]["fields"][0]["value"], "`status`: 2");
+    }
+
+    #[tokio::test]
+    async fn stats_queries_needs_admin() {
+        let store = Arc::new(MemoryStore::new());
+        for (query, closest, score) in [
+            ("Coldeve ", "Coldeve", 0.5),
+            ("coldeve", "Coldeve", 0.9),
+            ("xyz", "Frostfell", 0.1),
+        ] {
+            store
+                .log_server_query(ServerQuery {
+                    query: query.to_string(),
+                    resolved_name: None,
+                    closest_name: Some(closest.to_string()),
+                    score: Some(score),
+                    guild_id: Some(GUILD.to_string()),
+                })
+                .await
+                .unwrap();
+        }
+        let handler = handler(&store);
+
+        let (_, outcome) = respond(&handler, &command("stats", subcommand("queries"), false)).await;
+        assert_eq!(outcome.error.as_deref(), Some("Missing permission"));
+
+        let (response, outcome) =
+            respond(&handler, &command("stats", subcommand("queries"), true)).await;
+        assert!(outcome.error.is_none());
+        assert_eq!(
+            response["embeds"][0]["description"],
+            "`coldeve` ×2 → suggest alias for **Coldeve** (0.90)\n`xyz` ×1 → suggest alias for **Frostfell** (0.10)"
+        );
+    }
 
-    Ok(())
+    #[tokio::test]
+    async fn logs_are_anonymized_after_opting_out() {
+        let store = Arc::new(MemoryStore::new());
+        let handler = handler(&store);
+        let status = command("status", json!([]), false);
+
+        handler
+            .log_interaction(&status, CommandOutcome::default(), Duration::from_millis(5))
+            .await;
+        let logs = store.get_user_logs("1").await.unwrap();
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0].message_id.as_deref(), Some("300"));
+        assert_eq!(logs[0].latency_ms, Some(5));
+
+        store.delete_user_data("1").await.unwrap();
+        handler
+            .log_interaction(&status, CommandOutcome::default(), Duration::ZERO)
+            .await;
+        assert!(store.get_user_logs("1").await.unwrap().is_empty());
+        let logs = store.get_user_logs("0").await.unwrap();
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0].user_name, "anonymous");
+        assert_eq!(logs[0].message_id, None);
+        assert_eq!(logs[0].options, None);
+    }
+
+    #[tokio::test]
+    async fn detection_follows_settings() {
+        let store = Arc::new(MemoryStore::new());
+        let handler = handler(&store);
+        let guild = Some(GuildId::new(GUILD));
+
+        assert!(
+            handler
+                .detection_settings(guild, ChannelId::new(CHANNEL))
+                .await
+                .is_some()
+        );
+        assert!(
+            handler
+                .detection_settings(None, ChannelId::new(CHANNEL))
+                .await
+                .is_some()
+        );
+
+        handler
+            .settings
+            .update(GuildId::new(GUILD), |settings| {
+                settings.pcap_denied_channels = vec![CHANNEL.to_string()];
+                settings.reply_style = ReplyStyle::Embed;
+            })
+            .await
+            .unwrap();
+        handler.settings.set_pcap_in_dms(false).await.unwrap();
+
+        assert!(
+            handler
+                .detection_settings(guild, ChannelId::new(CHANNEL))
+                .await
+                .is_none()
+        );
+        let settings = handler
+            .detection_settings(guild, ChannelId::new(CHANNEL + 1))
+            .await;
+        assert_eq!(settings.map(|s| s.reply_style), Some(ReplyStyle::Embed));
+        assert!(
+            handler
+                .detection_settings(None, ChannelId::new(CHANNEL))
+                .await
+                .is_none()
+        );
+
+        // Saved through the store, not just cached
+        let saved = store.get_guild_settings(&GUILD.to_string()).await.unwrap();
+        assert_eq!(saved.pcap_denied_channels, vec![CHANNEL.to_string()]);
+    }
 }
@@ -0,0 +1,274 @@
+use serenity::all::Permissions;
+use serenity::builder::{
+    CreateCommand, CreateCommandOption, CreateEmbed, CreateInteractionResponseMessage,
+};
+use serenity::model::application::{
+    CommandInteraction, CommandOptionType, ResolvedOption, ResolvedValue,
+};
+use serenity::prelude::Context;
+use tracing::error;
+
+use crate::commands::{CommandOutcome, is_admin, is_bot_owner};
+use crate::db::Alias;
+use crate::servers::{fetch_servers, server_by_name};
+use crate::store::ServerStore;
+
+/// Keep the list comfortably under Discord's 4096 character description limit
+const MAX_LIST_LINES: usize = 50;
+
+pub fn register() -> CreateCommand {
+    let global_option = || {
+        CreateCommandOption::new(
+            CommandOptionType::Boolean,
+            "global",
+            "Apply to every server the bot is in (bot owner only)",
+        )
+    };
+
+    CreateCommand::new("alias")
+        .description("Manage nicknames for /server lookups")
+        .default_member_permissions(Permissions::MANAGE_GUILD)
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "add",
+                "Point a nickname at a server",
+            )
+            .add_sub_option(
+                CreateCommandOption::new(CommandOptionType::String, "alias", "The nickname")
+                    .required(true),
+            )
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::String,
+                    "server",
+                    "The server's exact name",
+                )
+                .required(true),
+            )
+            .add_sub_option(global_option()),
+        )
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::SubCommand, "remove", "Remove a nickname")
+                .add_sub_option(
+                    CreateCommandOption::new(CommandOptionType::String, "alias", "The nickname")
+                        .required(true),
+                )
+                .add_sub_option(global_option()),
+        )
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "list",
+            "Show the nicknames in effect here",
+        ))
+}
+
+fn reply(content: impl Into<String>) -> CreateInteractionResponseMessage {
+    CreateInteractionResponseMessage::new()
+        .content(content)
+        .ephemeral(true)
+}
+
+fn string_option<'a>(options: &[ResolvedOption<'a>], name: &str) -> Option<&'a str> {
+    options.iter().find_map(|opt| match opt.value {
+        ResolvedValue::String(value) if opt.name == name => Some(value),
+        _ => None,
+    })
+}
+
+async fn add(
+    store: &dyn ServerStore,
+    command: &CommandInteraction,
+    alias: Alias,
+) -> (String, CommandOutcome) {
+    let servers = match fetch_servers().await {
+        Ok(servers) => servers,
+        Err(e) => {
+            error!("Failed to fetch servers: {}", e);
+            return (
+                "Failed to fetch server list. Please try again later.".to_string(),
+                CommandOutcome::error(e),
+            );
+        }
+    };
+
+    // Store the canonical name so the alias keeps working regardless of case
+    let Some(server) = server_by_name(&servers, &alias.server_name) else {
+        return (
+            format!(
+                "There's no server named '{}'. Aliases need the server's exact name.",
+                alias.server_name
+            ),
+            CommandOutcome::error("Unknown server"),
+        );
+    };
+    let alias = Alias {
+        server_name: server.name.clone(),
+        ..alias
+    };
+
+    match store.add_alias(&alias, &command.user.id.to_string()).await {
+        Ok(()) => (
+            format!("`{}` now points to **{}**.", alias.alias, alias.server_name),
+            CommandOutcome {
+                detail: Some(format!("{} -> {}", alias.alias, alias.server_name)),
+                ..Default::default()
+            },
+        ),
+        Err(e) => {
+            error!("Failed to add alias: {:#}", e);
+            (
+                "Failed to save the alias. Please try again later.".to_string(),
+                CommandOutcome::error(format!("{e:#}")),
+            )
+        }
+    }
+}
+
+async fn remove(
+    store: &dyn ServerStore,
+    alias: &str,
+    guild_id: Option<&str>,
+) -> (String, CommandOutcome) {
+    match store.remove_alias(alias, guild_id).await {
+        Ok(true) => (
+            format!("Removed `{alias}`."),
+            CommandOutcome {
+                detail: Some(alias.to_string()),
+                ..Default::default()
+            },
+        ),
+        Ok(false) => (
+            format!("There's no alias `{alias}` to remove."),
+            CommandOutcome::error("Unknown alias"),
+        ),
+        Err(e) => {
+            error!("Failed to remove alias: {:#}", e);
+            (
+                "Failed to remove the alias. Please try again later.".to_string(),
+                CommandOutcome::error(format!("{e:#}")),
+            )
+        }
+    }
+}
+
+async fn list(
+    store: &dyn ServerStore,
+    guild_id: Option<&str>,
+) -> Result<CreateEmbed, CommandOutcome> {
+    let aliases = store.get_aliases(guild_id).await.map_err(|e| {
+        error!("Failed to fetch aliases: {:#}", e);
+        CommandOutcome::error(format!("{e:#}"))
+    })?;
+
+    let description = if aliases.is_empty() {
+        "No aliases yet. Add one with `/alias add`.".to_string()
+    } else {
+        aliases
+            .iter()
+            .take(MAX_LIST_LINES)
+            .map(|a| {
+                format!(
+                    "`{}` → **{}**{}",
+                    a.alias,
+                    a.server_name,
+                    if a.guild_id.is_none() {
+                        " (global)"
+                    } else {
+                        ""
+                    }
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+
+    Ok(CreateEmbed::new()
+        .title("Server aliases")
+        .description(description))
+}
+
+/// Handle `/alias <subcommand>`
+pub async fn run(
+    ctx: &Context,
+    store: &dyn ServerStore,
+    command: &CommandInteraction,
+) -> (CreateInteractionResponseMessage, CommandOutcome) {
+    let options = command.data.options();
+    let Some(ResolvedOption {
+        name,
+        value: ResolvedValue::SubCommand(sub_options),
+        ..
+    }) = options.first()
+    else {
+        return (
+            reply("Unknown subcommand"),
+            CommandOutcome::error("Unknown subcommand"),
+        );
+    };
+
+    if !is_admin(command) {
+        return (
+            reply("You need the Manage Server permission to use this."),
+            CommandOutcome::error("Missing permission"),
+        );
+    }
+
+    let guild_id = command.guild_id.map(|id| id.to_string());
+
+    let global = sub_options
+        .iter()
+        .any(|opt| opt.name == "global" && matches!(opt.value, ResolvedValue::Boolean(true)));
+    if global && !is_bot_owner(ctx, command).await {
+        return (
+            reply("Only the bot owner can manage global aliases."),
+            CommandOutcome::error("Missing permission"),
+        );
+    }
+    let scope = if global { None } else { guild_id.clone() };
+
+    // Aliases are matched case-insensitively, so store them normalized
+    let alias_name = string_option(sub_options, "alias").map(|a| a.trim().to_lowercase());
+
+    let (content, outcome) = match (*name, alias_name) {
+        ("add", Some(alias)) => {
+            let server_name = string_option(sub_options, "server")
+                .unwrap_or_default()
+                .to_string();
+            let alias = Alias {
+                alias,
+                server_name,
+                guild_id: scope,
+            };
+            add(store, command, alias).await
+        }
+        ("remove", Some(alias)) => remove(store, &alias, scope.as_deref()).await,
+        ("list", _) => {
+            return match list(store, guild_id.as_deref()).await {
+                Ok(embed) => (
+                    CreateInteractionResponseMessage::new()
+                        .embed(embed)
+                        .ephemeral(true),
+                    CommandOutcome {
+                        detail: Some("list".to_string()),
+                        ..Default::default()
+                    },
+                ),
+                Err(outcome) => (
+                    reply("Failed to fetch aliases. Please try again later."),
+                    outcome,
+                ),
+            };
+        }
+        ("add" | "remove", None) => (
+            "Please give an alias.".to_string(),
+            CommandOutcome::error("Missing alias option"),
+        ),
+        _ => (
+            "Unknown subcommand".to_string(),
+            CommandOutcome::error("Unknown subcommand"),
+        ),
+    };
+
+    (reply(content), outcome)
+}
@@ -0,0 +1,355 @@
+use serenity::all::Permissions;
+use serenity::builder::{
+    CreateCommand, CreateCommandOption, CreateEmbed, CreateInteractionResponseMessage,
+};
+use serenity::model::application::{
+    CommandInteraction, CommandOptionType, ResolvedOption, ResolvedValue,
+};
+use serenity::model::channel::ChannelType;
+use serenity::prelude::Context;
+use tracing::error;
+
+use crate::commands::{CommandOutcome, is_admin, is_bot_owner};
+use crate::db::{GuildSettings, ReplyStyle};
+use crate::servers::{fetch_servers, find_server};
+use crate::settings::SettingsCache;
+use crate::store::ServerStore;
+
+pub fn register() -> CreateCommand {
+    let channel_option = || {
+        CreateCommandOption::new(CommandOptionType::Channel, "channel", "The channel")
+            .channel_types(vec![ChannelType::Text, ChannelType::PublicThread])
+            .required(true)
+    };
+
+    CreateCommand::new("config")
+        .description("Configure the bot for this server")
+        .default_member_permissions(Permissions::MANAGE_GUILD)
+        .dm_permission(false)
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "show",
+            "Show the current settings",
+        ))
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommandGroup,
+                "pcap",
+                "Which channels captures are detected in",
+            )
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "allow",
+                    "Detect captures in a channel (limits detection to allowed channels)",
+                )
+                .add_sub_option(channel_option()),
+            )
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "deny",
+                    "Never detect captures in a channel",
+                )
+                .add_sub_option(channel_option()),
+            )
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "remove",
+                    "Take a channel off the allow and deny lists",
+                )
+                .add_sub_option(channel_option()),
+            )
+            .add_sub_option(CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "reset",
+                "Detect captures in every channel",
+            ))
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "dms",
+                    "Detect captures sent to the bot directly (bot owner only)",
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Boolean,
+                        "enabled",
+                        "Whether to detect captures in DMs",
+                    )
+                    .required(true),
+                ),
+            ),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "default-server",
+                "Server /server looks up when no name is given",
+            )
+            .add_sub_option(CreateCommandOption::new(
+                CommandOptionType::String,
+                "server",
+                "Server name (leave out to clear)",
+            )),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "reply-style",
+                "How replies are formatted",
+            )
+            .add_sub_option(
+                CreateCommandOption::new(CommandOptionType::String, "style", "Reply style")
+                    .add_string_choice("Plain text", ReplyStyle::Text.as_str())
+                    .add_string_choice("Embed", ReplyStyle::Embed.as_str())
+                    .required(true),
+            ),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "auto-summarize",
+                "Summarize captures when they're posted",
+            )
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::Boolean,
+                    "enabled",
+                    "Whether to summarize captures",
+                )
+                .required(true),
+            ),
+        )
+}
+
+fn reply(content: impl Into<String>) -> CreateInteractionResponseMessage {
+    CreateInteractionResponseMessage::new()
+        .content(content)
+        .ephemeral(true)
+}
+
+/// Render channel IDs as mentions
+fn channel_list(channels: &[String], empty: &str) -> String {
+    if channels.is_empty() {
+        return empty.to_string();
+    }
+
+    channels
+        .iter()
+        .map(|id| format!("<#{id}>"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn settings_embed(settings: &GuildSettings, pcap_in_dms: bool) -> CreateEmbed {
+    CreateEmbed::new()
+        .title("Settings")
+        .field(
+            "Captures detected in",
+            channel_list(&settings.pcap_allowed_channels, "Every channel"),
+            false,
+        )
+        .field(
+            "Captures ignored in",
+            channel_list(&settings.pcap_denied_channels, "No channels"),
+            false,
+        )
+        .field(
+            "Captures in DMs",
+            if pcap_in_dms { "On" } else { "Off" },
+            true,
+        )
+        .field(
+            "Default server",
+            settings.default_server.as_deref().unwrap_or("None"),
+            true,
+        )
+        .field("Reply style", settings.reply_style.as_str(), true)
+        .field(
+            "Auto-summarize",
+            if settings.auto_summarize { "On" } else { "Off" },
+            true,
+        )
+}
+
+/// Resolve a server name the same way `/server` would, returning its
+/// canonical name
+async fn resolve_server(
+    store: &dyn ServerStore,
+    guild_id: &str,
+    query: &str,
+) -> Result<String, String> {
+    let servers = fetch_servers().await?;
+    let aliases = store
+        .get_aliases(Some(guild_id))
+        .await
+        .map_err(|e| format!("Failed to fetch aliases: {e:#}"))?;
+
+    find_server(&servers, &aliases, query)
+        .server
+        .map(|s| s.name.clone())
+        .ok_or_else(|| format!("Server '{query}' not found"))
+}
+
+/// Handle `/config <subcommand>`
+pub async fn run(
+    ctx: &Context,
+    store: &dyn ServerStore,
+    settings: &SettingsCache,
+    command: &CommandInteraction,
+) -> (CreateInteractionResponseMessage, CommandOutcome) {
+    let Some(guild_id) = command.guild_id else {
+        return (
+            reply("Settings can only be changed inside a server."),
+            CommandOutcome::error("Not in a guild"),
+        );
+    };
+
+    if !is_admin(command) {
+        return (
+            reply("You need the Manage Server permission to use this."),
+            CommandOutcome::error("Missing permission"),
+        );
+    }
+
+    let options = command.data.options();
+    let (name, sub_options) = match options.first() {
+        Some(ResolvedOption {
+            name: group,
+            value: ResolvedValue::SubCommandGroup(subcommands),
+            ..
+        }) => match subcommands.first() {
+            Some(ResolvedOption {
+                name,
+                value: ResolvedValue::SubCommand(sub_options),
+                ..
+            }) => (format!("{group} {name}"), sub_options.as_slice()),
+            _ => (group.to_string(), [].as_slice()),
+        },
+        Some(ResolvedOption {
+            name,
+            value: ResolvedValue::SubCommand(sub_options),
+            ..
+        }) => (name.to_string(), sub_options.as_slice()),
+        _ => (String::new(), [].as_slice()),
+    };
+
+    let channel = sub_options.iter().find_map(|opt| match opt.value {
+        ResolvedValue::Channel(channel) => Some(channel.id.to_string()),
+        _ => None,
+    });
+    let string = sub_options.iter().find_map(|opt| match opt.value {
+        ResolvedValue::String(value) => Some(value),
+        _ => None,
+    });
+    let boolean = sub_options.iter().find_map(|opt| match opt.value {
+        ResolvedValue::Boolean(value) => Some(value),
+        _ => None,
+    });
+
+    let result = match (name.as_str(), channel, string, boolean) {
+        ("show", ..) => Ok(settings.get(Some(guild_id)).await),
+        ("pcap allow", Some(channel), ..) => {
+            settings
+                .update(guild_id, |s| {
+                    s.pcap_denied_channels.retain(|c| *c != channel);
+                    if !s.pcap_allowed_channels.contains(&channel) {
+                        s.pcap_allowed_channels.push(channel);
+                    }
+                })
+                .await
+        }
+        ("pcap deny", Some(channel), ..) => {
+            settings
+                .update(guild_id, |s| {
+                    s.pcap_allowed_channels.retain(|c| *c != channel);
+                    if !s.pcap_denied_channels.contains(&channel) {
+                        s.pcap_denied_channels.push(channel);
+                    }
+                })
+                .await
+        }
+        ("pcap remove", Some(channel), ..) => {
+            settings
+                .update(guild_id, |s| {
+                    s.pcap_allowed_channels.retain(|c| *c != channel);
+                    s.pcap_denied_channels.retain(|c| *c != channel);
+                })
+                .await
+        }
+        ("pcap reset", ..) => {
+            settings
+                .update(guild_id, |s| {
+                    s.pcap_allowed_channels.clear();
+                    s.pcap_denied_channels.clear();
+                })
+                .await
+        }
+        ("pcap dms", _, _, Some(enabled)) => {
+            // DMs aren't tied to a guild, so this one is for the bot owner
+            if !is_bot_owner(ctx, command).await {
+                return (
+                    reply("Only the bot owner can change DM detection."),
+                    CommandOutcome::error("Missing permission"),
+                );
+            }
+            match settings.set_pcap_in_dms(enabled).await {
+                Ok(()) => Ok(settings.get(Some(guild_id)).await),
+                Err(e) => Err(e),
+            }
+        }
+        ("default-server", _, server, _) => {
+            let server = match server {
+                Some(query) => match resolve_server(store, &guild_id.to_string(), query).await {
+                    Ok(name) => Some(name),
+                    Err(e) => return (reply(format!("{e}.")), CommandOutcome::error(e)),
+                },
+                None => None,
+            };
+            settings
+                .update(guild_id, |s| s.default_server = server)
+                .await
+        }
+        ("reply-style", _, Some(style), _) => match ReplyStyle::parse(style) {
+            Some(style) => settings.update(guild_id, |s| s.reply_style = style).await,
+            None => {
+                return (
+                    reply("Unknown reply style."),
+                    CommandOutcome::error("Unknown reply style"),
+                );
+            }
+        },
+        ("auto-summarize", _, _, Some(enabled)) => {
+            settings
+                .update(guild_id, |s| s.auto_summarize = enabled)
+                .await
+        }
+        _ => {
+            return (
+                reply("Unknown subcommand"),
+                CommandOutcome::error("Unknown subcommand"),
+            );
+        }
+    };
+
+    match result {
+        Ok(current) => (
+            CreateInteractionResponseMessage::new()
+                .embed(settings_embed(&current, settings.pcap_in_dms().await))
+                .ephemeral(true),
+            CommandOutcome {
+                detail: Some(name),
+                ..Default::default()
+            },
+        ),
+        Err(e) => {
+            error!("Failed to update settings: {:#}", e);
+            (
+                reply("Failed to save settings. Please try again later."),
+                CommandOutcome::error(format!("{e:#}")),
+            )
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use serenity::model::application::CommandInteraction;
+use serenity::prelude::Context;
+use tracing::error;
+
+pub mod alias;
+pub mod config;
+pub mod privacy;
+pub mod stats;
+
+/// What a command did, recorded in the command log
+#[derive(Debug, Default)]
+pub struct CommandOutcome {
+    /// Set when the command failed
+    pub error: Option<String>,
+    /// Command-specific context, e.g. which server a query resolved to
+    pub detail: Option<String>,
+}
+
+impl CommandOutcome {
+    pub fn error(message: impl ToString) -> Self {
+        Self {
+            error: Some(message.to_string()),
+            detail: None,
+        }
+    }
+}
+
+/// Whether the invoking member may use admin-only commands
+///
+/// Admin commands are only available inside guilds, to members with the
+/// Manage Server permission.
+pub fn is_admin(command: &CommandInteraction) -> bool {
+    command
+        .member
+        .as_ref()
+        .and_then(|member| member.permissions)
+        .is_some_and(|permissions| permissions.manage_guild())
+}
+
+/// Whether the invoking user owns the bot application
+///
+/// Used for changes that affect every guild, such as global aliases.
+pub async fn is_bot_owner(ctx: &Context, command: &CommandInteraction) -> bool {
+    match ctx.http.get_current_application_info().await {
+        Ok(info) => info.owner.is_some_and(|owner| owner.id == command.user.id),
+        Err(e) => {
+            error!("Failed to fetch application info: {}", e);
+            false
+        }
+    }
+}
@@ -0,0 +1,159 @@
+use serde::Serialize;
+use serenity::builder::{
+    CreateAttachment, CreateCommand, CreateCommandOption, CreateInteractionResponseMessage,
+    CreateMessage,
+};
+use serenity::model::application::{
+    CommandInteraction, CommandOptionType, ResolvedOption, ResolvedValue,
+};
+use serenity::prelude::Context;
+use tracing::{error, info};
+
+use crate::commands::CommandOutcome;
+use crate::db::{UserLog, UserStats};
+use crate::store::CommandLogStore;
+
+/// Everything we store about a user, as sent by `/privacy export`
+#[derive(Serialize)]
+struct Export {
+    exported_at: i64,
+    stats: UserStats,
+    logs: Vec<UserLog>,
+}
+
+pub fn register() -> CreateCommand {
+    CreateCommand::new("privacy")
+        .description("See or delete what the bot stores about you")
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "export",
+            "Get a copy of everything the bot has logged about you",
+        ))
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "delete",
+                "Delete your logs and stop logging who you are",
+            )
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::Boolean,
+                    "confirm",
+                    "Yes, permanently delete my data",
+                )
+                .required(true),
+            ),
+        )
+}
+
+fn reply(content: impl Into<String>) -> CreateInteractionResponseMessage {
+    CreateInteractionResponseMessage::new()
+        .content(content)
+        .ephemeral(true)
+}
+
+async fn export(
+    ctx: &Context,
+    store: &dyn CommandLogStore,
+    command: &CommandInteraction,
+) -> anyhow::Result<CreateInteractionResponseMessage> {
+    let user_id = command.user.id.to_string();
+    let export = Export {
+        exported_at: chrono::Utc::now().timestamp(),
+        stats: store.get_user_stats(&user_id).await?,
+        logs: store.get_user_logs(&user_id).await?,
+    };
+    let json = serde_json::to_vec_pretty(&export)?;
+    let filename = format!("treestats-bot-{user_id}.json");
+
+    let dm = CreateMessage::new()
+        .content("Here's everything I've logged about you.")
+        .add_file(CreateAttachment::bytes(json.clone(), filename.clone()));
+
+    match command.user.direct_message(&ctx.http, dm).await {
+        Ok(_) => Ok(reply("I've sent you a DM with your data.")),
+        Err(e) => {
+            // An ephemeral reply is just as private as a DM
+            info!(
+                "Couldn't DM {} their data export, replying instead: {}",
+                command.user.id, e
+            );
+            Ok(reply("I couldn't DM you, so here's your data.")
+                .add_file(CreateAttachment::bytes(json, filename)))
+        }
+    }
+}
+
+/// Handle `/privacy <subcommand>`
+pub async fn run(
+    ctx: &Context,
+    store: &dyn CommandLogStore,
+    command: &CommandInteraction,
+) -> (CreateInteractionResponseMessage, CommandOutcome) {
+    let options = command.data.options();
+    let Some(ResolvedOption {
+        name,
+        value: ResolvedValue::SubCommand(sub_options),
+        ..
+    }) = options.first()
+    else {
+        return (
+            reply("Unknown subcommand"),
+            CommandOutcome::error("Unknown subcommand"),
+        );
+    };
+
+    match *name {
+        "export" => match export(ctx, store, command).await {
+            Ok(response) => (
+                response,
+                CommandOutcome {
+                    detail: Some("export".to_string()),
+                    ..Default::default()
+                },
+            ),
+            Err(e) => {
+                error!("Failed to export user data: {:#}", e);
+                (
+                    reply("Failed to export your data. Please try again later."),
+                    CommandOutcome::error(format!("{e:#}")),
+                )
+            }
+        },
+        "delete" => {
+            let confirmed = sub_options
+                .iter()
+                .any(|opt| matches!(opt.value, ResolvedValue::Boolean(true)));
+            if !confirmed {
+                return (
+                    reply("Nothing was deleted. Set `confirm` to True to delete your data."),
+                    CommandOutcome::error("Not confirmed"),
+                );
+            }
+
+            match store.delete_user_data(&command.user.id.to_string()).await {
+                Ok(deleted) => (
+                    reply(format!(
+                        "Deleted {deleted} log entr{}. From now on I'll log your commands without your name or ID.",
+                        if deleted == 1 { "y" } else { "ies" }
+                    )),
+                    CommandOutcome {
+                        detail: Some("delete".to_string()),
+                        ..Default::default()
+                    },
+                ),
+                Err(e) => {
+                    error!("Failed to delete user data: {:#}", e);
+                    (
+                        reply("Failed to delete your data. Please try again later."),
+                        CommandOutcome::error(format!("{e:#}")),
+                    )
+                }
+            }
+        }
+        _ => (
+            reply("Unknown subcommand"),
+            CommandOutcome::error("Unknown subcommand"),
+        ),
+    }
+}
@@ -0,0 +1,293 @@
+use serenity::builder::{
+    CreateCommand, CreateCommandOption, CreateEmbed, CreateEmbedFooter,
+    CreateInteractionResponseMessage,
+};
+use serenity::model::application::{
+    CommandInteraction, CommandOptionType, ResolvedOption, ResolvedValue,
+};
+use serenity::model::user::User;
+use serenity::utils::MessageBuilder;
+use tracing::error;
+
+use crate::commands::{CommandOutcome, is_admin};
+use crate::db::UnmatchedQuery;
+use crate::store::{CommandLogStore, ServerStore};
+
+const RECENT_DEFAULT_LIMIT: i64 = 10;
+const RECENT_MAX_LIMIT: i64 = 25;
+const USAGE_DAYS: i64 = 7;
+const QUERIES_LIMIT: i64 = 15;
+/// Keep embed fields comfortably under Discord's 1024 character limit
+const MAX_FIELD_LINES: usize = 10;
+
+pub fn register() -> CreateCommand {
+    CreateCommand::new("stats")
+        .description("Show bot usage statistics")
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "global",
+            "Overall bot usage",
+        ))
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "me",
+            "Your own usage",
+        ))
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "user",
+                "Usage for another user (admin only)",
+            )
+            .add_sub_option(
+                CreateCommandOption::new(CommandOptionType::User, "user", "The user to look up")
+                    .required(true),
+            ),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "recent",
+                "Most recent commands (admin only)",
+            )
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::Integer,
+                    "limit",
+                    "Number of entries to show",
+                )
+                .min_int_value(1)
+                .max_int_value(RECENT_MAX_LIMIT as u64),
+            ),
+        )
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "queries",
+            "Common /server queries that found nothing (admin only)",
+        ))
+}
+
+/// Render `(name, count)` pairs as embed field lines
+fn count_lines(rows: &[(String, i64)]) -> String {
+    if rows.is_empty() {
+        return "None yet".to_string();
+    }
+
+    rows.iter()
+        .take(MAX_FIELD_LINES)
+        .map(|(name, count)| format!("`{name}`: {count}"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+async fn global(store: &dyn CommandLogStore) -> anyhow::Result<CreateEmbed> {
+    let total = store.get_total_uses().await?;
+    let by_command = store.get_command_stats().await?;
+    let daily: Vec<(String, i64)> = store
+        .get_usage_over_time(USAGE_DAYS)
+        .await?
+        .into_iter()
+        .map(|day| (day.date, day.count))
+        .collect();
+
+    Ok(CreateEmbed::new()
+        .title("Bot usage")
+        .description(format!("**{total}** successful commands"))
+        .field("By command", count_lines(&by_command), true)
+        .field(format!("Last {USAGE_DAYS} days"), count_lines(&daily), true))
+}
+
+async fn user(store: &dyn CommandLogStore, user: &User) -> anyhow::Result<CreateEmbed> {
+    let stats = store.get_user_stats(&user.id.to_string()).await?;
+
+    let timestamp = |ts: Option<i64>| match ts {
+        Some(ts) => format!("<t:{ts}:R>"),
+        None => "Never".to_string(),
+    };
+
+    Ok(CreateEmbed::new()
+        .title(format!("Usage for {}", user.name))
+        .field("Total", stats.total_count.to_string(), true)
+        .field("First used", timestamp(stats.first_use), true)
+        .field("Last used", timestamp(stats.last_use), true)
+        .field("By command", count_lines(&stats.command_breakdown), false))
+}
+
+async fn recent(store: &dyn CommandLogStore, limit: i64) -> anyhow::Result<CreateEmbed> {
+    let logs = store.get_recent_logs(limit).await?;
+
+    let lines = if logs.is_empty() {
+        "None yet".to_string()
+    } else {
+        logs.iter()
+            .map(|log| {
+                format!(
+                    "<t:{}:R> `{}` by {} {}",
+                    log.timestamp,
+                    log.command_name,
+                    log.user_name,
+                    if log.success { "✅" } else { "❌" }
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+
+    Ok(CreateEmbed::new()
+        .title("Recent commands")
+        .description(lines))
+}
+
+/// Describe an unmatched query, escaping the text users typed
+fn query_line(query: &UnmatchedQuery) -> String {
+    let mut line = MessageBuilder::new();
+    line.push_mono_safe(query.query.replace('\n', " "))
+        .push(format!(" ×{}", query.count));
+    if let (Some(closest), Some(score)) = (&query.closest_name, query.score) {
+        line.push(" → suggest alias for ")
+            .push_bold_safe(closest.as_str())
+            .push(format!(" ({score:.2})"));
+    }
+    line.build()
+}
+
+async fn queries(servers: &dyn ServerStore) -> anyhow::Result<CreateEmbed> {
+    let unmatched = servers.get_unmatched_queries(QUERIES_LIMIT).await?;
+
+    let lines = if unmatched.is_empty() {
+        "Every /server query has found a server so far.".to_string()
+    } else {
+        unmatched
+            .iter()
+            .map(query_line)
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+
+    Ok(CreateEmbed::new()
+        .title("Unmatched /server queries")
+        .description(lines)
+        .footer(CreateEmbedFooter::new(
+            "Suggestions are the closest server by name similarity. Add one with /alias add.",
+        )))
+}
+
+/// Handle `/stats <subcommand>`
+pub async fn run(
+    store: &dyn CommandLogStore,
+    servers: &dyn ServerStore,
+    command: &CommandInteraction,
+) -> (CreateInteractionResponseMessage, CommandOutcome) {
+    let options = command.data.options();
+    let Some(ResolvedOption {
+        name,
+        value: ResolvedValue::SubCommand(sub_options),
+        ..
+    }) = options.first()
+    else {
+        return (
+            CreateInteractionResponseMessage::new().content("Unknown subcommand"),
+            CommandOutcome::error("Unknown subcommand"),
+        );
+    };
+
+    if matches!(*name, "user" | "recent" | "queries") && !is_admin(command) {
+        return (
+            CreateInteractionResponseMessage::new()
+                .content("You need the Manage Server permission to use this.")
+                .ephemeral(true),
+            CommandOutcome::error("Missing permission"),
+        );
+    }
+
+    let embed = match *name {
+        "global" => global(store).await,
+        "me" => user(store, &command.user).await,
+        "user" => {
+            let target = sub_options.iter().find_map(|opt| match opt.value {
+                ResolvedValue::User(user, _) => Some(user),
+                _ => None,
+            });
+            match target {
+                Some(target) => user(store, target).await,
+                None => {
+                    return (
+                        CreateInteractionResponseMessage::new().content("Please pick a user."),
+                        CommandOutcome::error("Missing user option"),
+                    );
+                }
+            }
+        }
+        "recent" => {
+            let limit = sub_options
+                .iter()
+                .find_map(|opt| match opt.value {
+                    ResolvedValue::Integer(limit) => Some(limit),
+                    _ => None,
+                })
+                .unwrap_or(RECENT_DEFAULT_LIMIT)
+                .clamp(1, RECENT_MAX_LIMIT);
+            recent(store, limit).await
+        }
+        "queries" => queries(servers).await,
+        _ => {
+            return (
+                CreateInteractionResponseMessage::new().content("Unknown subcommand"),
+                CommandOutcome::error("Unknown subcommand"),
+            );
+        }
+    };
+
+    match embed {
+        // Everything but the global summary is about specific people
+        Ok(embed) => (
+            CreateInteractionResponseMessage::new()
+                .embed(embed)
+                .ephemeral(*name != "global"),
+            CommandOutcome {
+                detail: Some(name.to_string()),
+                ..Default::default()
+            },
+        ),
+        Err(e) => {
+            error!("Failed to fetch stats: {:#}", e);
+            (
+                CreateInteractionResponseMessage::new()
+                    .content("Failed to fetch stats. Please try again later.")
+                    .ephemeral(true),
+                CommandOutcome::error(format!("{e:#}")),
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unmatched(query: &str, closest: Option<&str>) -> UnmatchedQuery {
+        UnmatchedQuery {
+            query: query.to_string(),
+            count: 3,
+            closest_name: closest.map(str::to_string),
+            score: closest.map(|_| 0.5),
+        }
+    }
+
+    #[test]
+    fn query_lines() {
+        assert_eq!(
+            query_line(&unmatched("coldeve", Some("Coldeve"))),
+            "`coldeve` ×3 → suggest alias for **Coldeve** (0.50)"
+        );
+        assert_eq!(query_line(&unmatched("xyz", None)), "`xyz` ×3");
+    }
+
+    #[test]
+    fn query_lines_escape_user_text() {
+        let line = query_line(&unmatched("a` **@everyone** <@1> `b\nc", None));
+        assert_eq!(line, "`a' **@\u{200B}everyone** <@1> 'b c` ×3");
+        // Only the two backticks around the query
+        assert_eq!(line.matches('`').count(), 2);
+    }
+}
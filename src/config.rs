@@ -0,0 +1,218 @@
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Deserializer};
+use tracing::info;
+
+use crate::backup::BackupConfig;
+use crate::db::{DatabaseConfig, Dialect};
+use crate::log_queue::LogQueueConfig;
+use crate::maintenance::MaintenanceConfig;
+
+/// Read when no `--config` is given, if it exists
+const DEFAULT_CONFIG_FILE: &str = "bot.toml";
+const DEFAULT_PORT: u16 = 3000;
+
+/// Everything configurable, loaded once at startup
+///
+/// Settings come from a TOML file (see bot.example.toml), then environment
+/// variables override them.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub port: u16,
+    /// Public URL of the web viewer; defaults to localhost on `port`
+    pub web_url: Option<String>,
+    pub discord_bot_token: Option<String>,
+    /// Serve captures from `<dir>/<channel_id>/<message_id>/` instead of
+    /// fetching them from Discord
+    pub fixtures_dir: Option<PathBuf>,
+    /// Build shown in logs, from `GIT_SHA_SHORT`
+    #[serde(skip)]
+    pub version: String,
+    pub database: DatabaseConfig,
+    pub log_queue: LogQueueConfig,
+    pub maintenance: MaintenanceConfig,
+    pub backup: BackupConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            port: DEFAULT_PORT,
+            web_url: None,
+            discord_bot_token: None,
+            fixtures_dir: None,
+            version: "unknown".to_string(),
+            database: DatabaseConfig::default(),
+            log_queue: LogQueueConfig::default(),
+            maintenance: MaintenanceConfig::default(),
+            backup: BackupConfig::default(),
+        }
+    }
+}
+
+/// Parse an optional environment variable
+fn env_var<T: FromStr>(name: &str) -> Result<Option<T>>
+where
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    match std::env::var(name) {
+        Ok(value) if !value.is_empty() => value
+            .parse()
+            .map(Some)
+            .with_context(|| format!("Invalid {name}: {value:?}")),
+        _ => Ok(None),
+    }
+}
+
+/// Replace `field` with an environment variable, if it's set
+fn override_from_env<T: FromStr>(field: &mut T, name: &str) -> Result<()>
+where
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    if let Some(value) = env_var(name)? {
+        *field = value;
+    }
+    Ok(())
+}
+
+/// Deserialize a string with `FromStr`, for types that aren't `Deserialize`
+pub fn from_str<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    String::deserialize(deserializer)?
+        .parse()
+        .map_err(serde::de::Error::custom)
+}
+
+impl Config {
+    /// Load `path` (or bot.toml, if present), apply environment overrides and
+    /// validate the result
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let path = match path {
+            Some(path) => Some(path),
+            None => Some(Path::new(DEFAULT_CONFIG_FILE)).filter(|path| path.exists()),
+        };
+
+        let mut config = match path {
+            Some(path) => {
+                let contents = std::fs::read_to_string(path)
+                    .with_context(|| format!("Failed to read {}", path.display()))?;
+                let config: Config = toml::from_str(&contents)
+                    .with_context(|| format!("Invalid config file {}", path.display()))?;
+                info!("Loaded config from {}", path.display());
+                config
+            }
+            None => Config::default(),
+        };
+
+        config.apply_env()?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    /// Apply overrides from environment variables
+    fn apply_env(&mut self) -> Result<()> {
+        override_from_env(&mut self.port, "PORT")?;
+        self.web_url = env_var("WEB_URL")?.or(self.web_url.take());
+        self.discord_bot_token = env_var("DISCORD_BOT_TOKEN")?.or(self.discord_bot_token.take());
+        self.fixtures_dir = env_var("FIXTURES_DIR")?.or(self.fixtures_dir.take());
+        override_from_env(&mut self.version, "GIT_SHA_SHORT")?;
+
+        let database = &mut self.database;
+        override_from_env(&mut database.url, "DATABASE_URL")?;
+        override_from_env(&mut database.max_connections, "DATABASE_MAX_CONNECTIONS")?;
+        override_from_env(&mut database.sqlite_journal_mode, "SQLITE_JOURNAL_MODE")?;
+        override_from_env(
+            &mut database.sqlite_busy_timeout_ms,
+            "SQLITE_BUSY_TIMEOUT_MS",
+        )?;
+        override_from_env(&mut database.sqlite_synchronous, "SQLITE_SYNCHRONOUS")?;
+
+        let log_queue = &mut self.log_queue;
+        override_from_env(&mut log_queue.capacity, "LOG_QUEUE_CAPACITY")?;
+        override_from_env(&mut log_queue.batch_size, "LOG_BATCH_SIZE")?;
+
+        let maintenance = &mut self.maintenance;
+        override_from_env(&mut maintenance.log_retention_days, "LOG_RETENTION_DAYS")?;
+        override_from_env(&mut maintenance.log_rollup, "LOG_ROLLUP")?;
+        override_from_env(
+            &mut maintenance.interval_hours,
+            "MAINTENANCE_INTERVAL_HOURS",
+        )?;
+        override_from_env(
+            &mut maintenance.vacuum_interval_days,
+            "VACUUM_INTERVAL_DAYS",
+        )?;
+
+        let backup = &mut self.backup;
+        backup.dir = env_var("BACKUP_DIR")?.or(backup.dir.take());
+        override_from_env(&mut backup.interval_hours, "BACKUP_INTERVAL_HOURS")?;
+        override_from_env(&mut backup.keep, "BACKUP_KEEP")?;
+
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.port == 0 {
+            bail!("port must not be 0");
+        }
+        if let Some(url) = &self.web_url
+            && !(url.starts_with("http://") || url.starts_with("https://"))
+        {
+            bail!("web_url must be an http:// or https:// URL, got {url:?}");
+        }
+        if let Some(dir) = &self.fixtures_dir
+            && !dir.is_dir()
+        {
+            bail!("fixtures_dir {} is not a directory", dir.display());
+        }
+
+        let dialect = Dialect::from_url(&self.database.url).context("Invalid database.url")?;
+        if self.database.max_connections == 0 {
+            bail!("database.max_connections must be at least 1");
+        }
+
+        if self.log_queue.capacity == 0 || self.log_queue.batch_size == 0 {
+            bail!("log_queue.capacity and log_queue.batch_size must be at least 1");
+        }
+
+        if self.maintenance.log_retention_days < 0 {
+            bail!("maintenance.log_retention_days must not be negative (0 keeps logs forever)");
+        }
+        if self.maintenance.interval_hours == 0 {
+            bail!("maintenance.interval_hours must be at least 1");
+        }
+
+        if self.backup.interval_hours == 0 || self.backup.keep == 0 {
+            bail!("backup.interval_hours and backup.keep must be at least 1");
+        }
+        if self.backup.dir.is_some() && dialect != Dialect::Sqlite {
+            bail!("backup.dir only works with SQLite; use pg_dump to back up PostgreSQL");
+        }
+
+        Ok(())
+    }
+
+    /// The Discord bot token, which running the bot requires
+    pub fn discord_bot_token(&self) -> Result<&str> {
+        match self.discord_bot_token.as_deref() {
+            Some(token) if !token.trim().is_empty() => Ok(token),
+            _ => bail!("DISCORD_BOT_TOKEN (or discord_bot_token in the config file) is required"),
+        }
+    }
+
+    /// The public URL of the web viewer
+    pub fn web_url(&self) -> String {
+        self.web_url
+            .clone()
+            .unwrap_or_else(|| format!("http://localhost:{}", self.port))
+    }
+}
@@ -2,6 +2,8 @@ use axum::http::StatusCode;
 use serde::Deserialize;
 use tracing::{debug, error, warn};
 
+use crate::metrics;
+
 const DISCORD_API_BASE: &str = "https://discord.com/api/v9";
 const MAX_ATTACHMENT_SIZE: usize = 10 * 1024 * 1024; // MB
 const TOKEN_PREFIX: &str = "Bot "; // Bot token prefix (required by Discord API)
@@ -31,8 +33,10 @@ pub fn is_valid_snowflake(id: &str) -> bool {
     !id.is_empty() && id.len() >= 17 && id.len() <= 19 && id.chars().all(|c| c.is_ascii_digit())
 }
 
-fn is_pcap_file(filename: &str) -> bool {
-    filename.ends_with(".pcap")
+/// Whether a filename looks like a packet capture (.pcap or .pcapng)
+pub fn is_pcap_file(filename: &str) -> bool {
+    let filename = filename.to_lowercase();
+    filename.ends_with(".pcap") || filename.ends_with(".pcapng")
 }
 
 // Fetch message from Discord API
@@ -65,6 +69,9 @@ pub async fn fetch_message(
         .send()
         .await
         .map_err(|e| {
+            metrics::DISCORD_API_RESPONSES
+                .with_label_values(&["error"])
+                .inc();
             error!("Failed to fetch Discord message: {}", e);
             (
                 StatusCode::INTERNAL_SERVER_ERROR,
@@ -72,6 +79,10 @@ pub async fn fetch_message(
             )
         })?;
 
+    metrics::DISCORD_API_RESPONSES
+        .with_label_values(&[response.status().as_str()])
+        .inc();
+
     if response.status().is_success() {
         let message = response.json::<DiscordMessage>().await.map_err(|e| {
             error!("Failed to parse Discord message: {}", e);
@@ -128,6 +139,8 @@ pub async fn fetch_message(
 pub async fn download_attachment(url: &str) -> Result<Vec<u8>, (StatusCode, String)> {
     debug!("Downloading attachment from: {}", url);
 
+    let _timer = metrics::ATTACHMENT_DURATION.start_timer();
+
     let client = reqwest::Client::new();
     let response = client.get(url).send().await.map_err(|e| {
         error!("Failed to download attachment: {}", e);
@@ -156,6 +169,8 @@ pub async fn download_attachment(url: &str) -> Result<Vec<u8>, (StatusCode, Stri
             )
         })?;
 
+        metrics::ATTACHMENT_BYTES.inc_by(bytes.len() as u64);
+
         Ok(bytes.to_vec())
     } else {
         error!("Attachment download error: {}", response.status());
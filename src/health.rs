@@ -0,0 +1,120 @@
+use std::sync::Arc;
+
+use serde::Serialize;
+use serenity::gateway::{ConnectionStage, ShardManager};
+
+use crate::db::Database;
+use crate::servers;
+
+/// What readiness checks look at; parts that aren't running are `None`
+#[derive(Clone, Default)]
+pub struct Health {
+    pub shard_manager: Option<Arc<ShardManager>>,
+    pub db: Option<Database>,
+}
+
+#[derive(Serialize)]
+pub struct ShardStatus {
+    pub id: u32,
+    pub stage: String,
+    pub latency_ms: Option<u128>,
+}
+
+#[derive(Serialize)]
+pub struct GatewayStatus {
+    /// `disabled` when the bot isn't running
+    pub status: &'static str,
+    pub shards: Vec<ShardStatus>,
+}
+
+#[derive(Serialize)]
+pub struct DatabaseStatus {
+    /// `disabled` when the bot isn't running
+    pub status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct ServersStatus {
+    /// Unix timestamp of the last successful servers.json fetch
+    pub last_success: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub struct Readiness {
+    pub ready: bool,
+    pub gateway: GatewayStatus,
+    pub database: DatabaseStatus,
+    pub servers: ServersStatus,
+}
+
+impl Health {
+    async fn gateway(&self) -> GatewayStatus {
+        let Some(shard_manager) = &self.shard_manager else {
+            return GatewayStatus {
+                status: "disabled",
+                shards: Vec::new(),
+            };
+        };
+
+        let runners = shard_manager.runners.lock().await;
+        let mut shards: Vec<ShardStatus> = runners
+            .iter()
+            .map(|(id, runner)| ShardStatus {
+                id: id.0,
+                stage: runner.stage.to_string(),
+                latency_ms: runner.latency.map(|latency| latency.as_millis()),
+            })
+            .collect();
+        shards.sort_by_key(|shard| shard.id);
+
+        let connected = !runners.is_empty()
+            && runners
+                .values()
+                .all(|runner| runner.stage == ConnectionStage::Connected);
+
+        GatewayStatus {
+            status: if connected { "ok" } else { "connecting" },
+            shards,
+        }
+    }
+
+    async fn database(&self) -> DatabaseStatus {
+        let Some(db) = &self.db else {
+            return DatabaseStatus {
+                status: "disabled",
+                error: None,
+            };
+        };
+
+        match db.ping().await {
+            Ok(()) => DatabaseStatus {
+                status: "ok",
+                error: None,
+            },
+            Err(e) => DatabaseStatus {
+                status: "error",
+                error: Some(format!("{e:#}")),
+            },
+        }
+    }
+
+    /// Check whether everything that's running can do its job
+    ///
+    /// servers.json is reported but doesn't affect readiness: it's an outside
+    /// service, and commands already handle it being down.
+    pub async fn readiness(&self) -> Readiness {
+        let gateway = self.gateway().await;
+        let database = self.database().await;
+
+        Readiness {
+            ready: gateway.status != "connecting" && database.status != "error",
+            gateway,
+            database,
+            servers: ServersStatus {
+                last_success: servers::last_fetch(),
+            },
+        }
+    }
+}
@@ -0,0 +1,296 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use anyhow::Result;
+use serde::Deserialize;
+use serenity::async_trait;
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
+
+use crate::db::{CommandLog, DailyUsage, RecentLog, UserLog, UserStats};
+use crate::metrics;
+use crate::store::CommandLogStore;
+
+const DEFAULT_CAPACITY: usize = 1000;
+const DEFAULT_BATCH_SIZE: usize = 100;
+/// Warn about every this many dropped logs, after the first
+const DROPPED_WARN_EVERY: u64 = 100;
+
+/// How many logs can wait to be written, and how many are written at once
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogQueueConfig {
+    pub capacity: usize,
+    pub batch_size: usize,
+}
+
+impl Default for LogQueueConfig {
+    fn default() -> Self {
+        Self {
+            capacity: DEFAULT_CAPACITY,
+            batch_size: DEFAULT_BATCH_SIZE,
+        }
+    }
+}
+
+/// A [`CommandLogStore`] that queues logs for a background task to write in
+/// batches, so logging never waits on the database
+///
+/// If the queue is full the log is dropped and counted rather than waited
+/// for, as are logs the store refuses. Everything else goes straight to the
+/// underlying store.
+pub struct LogQueue {
+    store: Arc<dyn CommandLogStore>,
+    sender: mpsc::Sender<CommandLog>,
+    dropped: Arc<AtomicU64>,
+}
+
+/// The background task writing a [`LogQueue`]'s logs
+pub struct LogWriter {
+    shutdown: oneshot::Sender<()>,
+    handle: JoinHandle<()>,
+}
+
+impl LogQueue {
+    /// Start writing queued logs to `store`
+    pub fn spawn(
+        store: Arc<dyn CommandLogStore>,
+        config: LogQueueConfig,
+    ) -> (Arc<Self>, LogWriter) {
+        info!(
+            "Command log queue holds {} logs, written in batches of up to {}",
+            config.capacity, config.batch_size
+        );
+
+        let (sender, receiver) = mpsc::channel(config.capacity);
+        let (shutdown, shutdown_receiver) = oneshot::channel();
+        let dropped = Arc::new(AtomicU64::new(0));
+
+        let handle = tokio::spawn(write_batches(
+            store.clone(),
+            receiver,
+            shutdown_receiver,
+            config.batch_size,
+            dropped.clone(),
+        ));
+
+        let queue = Arc::new(Self {
+            store,
+            sender,
+            dropped,
+        });
+
+        (queue, LogWriter { shutdown, handle })
+    }
+
+    /// How many logs were dropped because the queue was full or they
+    /// couldn't be written
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl LogWriter {
+    /// Stop accepting logs and wait for the queued ones to be written
+    pub async fn flush(self) {
+        // The task may have already stopped if every sender was dropped
+        let _ = self.shutdown.send(());
+
+        if let Err(e) = self.handle.await {
+            error!("Command log writer failed: {}", e);
+        }
+    }
+}
+
+/// Write queued logs until the queue is closed and empty
+async fn write_batches(
+    store: Arc<dyn CommandLogStore>,
+    mut receiver: mpsc::Receiver<CommandLog>,
+    mut shutdown: oneshot::Receiver<()>,
+    batch_size: usize,
+    dropped: Arc<AtomicU64>,
+) {
+    let mut batch = Vec::with_capacity(batch_size);
+    let mut closing = false;
+
+    loop {
+        tokio::select! {
+            received = receiver.recv_many(&mut batch, batch_size) => {
+                // Only empty once closed and drained
+                if received == 0 {
+                    break;
+                }
+
+                let logs = std::mem::take(&mut batch);
+                write_batch(store.as_ref(), logs, &dropped).await;
+            }
+            _ = &mut shutdown, if !closing => {
+                closing = true;
+                receiver.close();
+            }
+        }
+    }
+
+    info!("Command log queue flushed");
+}
+
+/// Write a batch in one transaction, falling back to one log at a time if
+/// that fails so a single bad log doesn't lose the rest
+async fn write_batch(store: &dyn CommandLogStore, logs: Vec<CommandLog>, dropped: &AtomicU64) {
+    let count = logs.len();
+    let Err(e) = store.log_commands(logs.clone()).await else {
+        return;
+    };
+    warn!(
+        "Failed to write {} command log(s) as a batch, retrying one at a time: {:#}",
+        count, e
+    );
+
+    for log in logs {
+        let command_name = log.command_name.clone();
+        if let Err(e) = store.log_command(log).await {
+            error!("Dropped a {} command log: {:#}", command_name, e);
+            dropped.fetch_add(1, Ordering::Relaxed);
+            metrics::COMMAND_LOGS_DROPPED.inc();
+        }
+    }
+}
+
+#[async_trait]
+impl CommandLogStore for LogQueue {
+    async fn log_command(&self, log: CommandLog) -> Result<()> {
+        if let Err(e) = self.sender.try_send(log) {
+            let dropped = self.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+            metrics::COMMAND_LOGS_DROPPED.inc();
+            if dropped == 1 || dropped.is_multiple_of(DROPPED_WARN_EVERY) {
+                warn!(
+                    "Dropped a command log ({}); {} dropped so far",
+                    match e {
+                        mpsc::error::TrySendError::Full(_) => "queue full",
+                        mpsc::error::TrySendError::Closed(_) => "queue closed",
+                    },
+                    dropped
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn get_command_stats(&self) -> Result<Vec<(String, i64)>> {
+        self.store.get_command_stats().await
+    }
+
+    async fn get_recent_logs(&self, limit: i64) -> Result<Vec<RecentLog>> {
+        self.store.get_recent_logs(limit).await
+    }
+
+    async fn get_total_uses(&self) -> Result<i64> {
+        self.store.get_total_uses().await
+    }
+
+    async fn get_user_stats(&self, user_id: &str) -> Result<UserStats> {
+        self.store.get_user_stats(user_id).await
+    }
+
+    async fn get_usage_over_time(&self, days: i64) -> Result<Vec<DailyUsage>> {
+        self.store.get_usage_over_time(days).await
+    }
+
+    async fn get_user_logs(&self, user_id: &str) -> Result<Vec<UserLog>> {
+        self.store.get_user_logs(user_id).await
+    }
+
+    async fn delete_user_data(&self, user_id: &str) -> Result<u64> {
+        self.store.delete_user_data(user_id).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory_store::MemoryStore;
+
+    /// Refuses every batch and any log for the `bad` command
+    #[derive(Default)]
+    struct FlakyStore {
+        inner: MemoryStore,
+    }
+
+    #[async_trait]
+    impl CommandLogStore for FlakyStore {
+        async fn log_command(&self, log: CommandLog) -> Result<()> {
+            if log.command_name == "bad" {
+                anyhow::bail!("bad log");
+            }
+            self.inner.log_command(log).await
+        }
+
+        async fn log_commands(&self, _logs: Vec<CommandLog>) -> Result<()> {
+            anyhow::bail!("batch failed")
+        }
+
+        async fn get_command_stats(&self) -> Result<Vec<(String, i64)>> {
+            self.inner.get_command_stats().await
+        }
+
+        async fn get_recent_logs(&self, limit: i64) -> Result<Vec<RecentLog>> {
+            self.inner.get_recent_logs(limit).await
+        }
+
+        async fn get_total_uses(&self) -> Result<i64> {
+            self.inner.get_total_uses().await
+        }
+
+        async fn get_user_stats(&self, user_id: &str) -> Result<UserStats> {
+            self.inner.get_user_stats(user_id).await
+        }
+
+        async fn get_usage_over_time(&self, days: i64) -> Result<Vec<DailyUsage>> {
+            self.inner.get_usage_over_time(days).await
+        }
+
+        async fn get_user_logs(&self, user_id: &str) -> Result<Vec<UserLog>> {
+            self.inner.get_user_logs(user_id).await
+        }
+
+        async fn delete_user_data(&self, user_id: &str) -> Result<u64> {
+            self.inner.delete_user_data(user_id).await
+        }
+    }
+
+    fn log(command_name: &str) -> CommandLog {
+        CommandLog {
+            command_name: command_name.to_string(),
+            user_id: "1".to_string(),
+            user_name: "alice".to_string(),
+            channel_id: "2".to_string(),
+            guild_id: None,
+            message_id: Some("3".to_string()),
+            success: true,
+            error_message: None,
+            options: None,
+            latency_ms: None,
+            detail: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn failed_batches_are_written_one_at_a_time() {
+        let store = Arc::new(FlakyStore::default());
+        let (queue, writer) = LogQueue::spawn(store.clone(), LogQueueConfig::default());
+        let before = metrics::COMMAND_LOGS_DROPPED.get();
+
+        for name in ["status", "bad", "server"] {
+            queue.log_command(log(name)).await.unwrap();
+        }
+        writer.flush().await;
+
+        let written = store.get_user_logs("1").await.unwrap();
+        let names: Vec<&str> = written.iter().map(|l| l.command_name.as_str()).collect();
+        assert_eq!(names, ["status", "server"]);
+        assert_eq!(queue.dropped(), 1);
+        assert!(metrics::COMMAND_LOGS_DROPPED.get() > before);
+    }
+}
@@ -1,14 +1,78 @@
 use std::error::Error;
+use std::path::PathBuf;
+use std::process::ExitCode;
 
+use clap::{Parser, Subcommand};
 use log::info;
 
-use crate::web::create_router;
+use crate::config::Config;
+use crate::web::{CaptureSource, create_router};
 
+mod backup;
 mod bot;
+mod commands;
+mod config;
 mod db;
 mod discord;
+mod health;
+mod log_queue;
+mod maintenance;
+#[cfg(test)]
+mod memory_store;
+mod metrics;
+mod pcap;
+mod protocol;
+mod scrub;
+mod servers;
+mod settings;
+mod store;
+mod supervisor;
 mod web;
 
+#[derive(Parser)]
+#[command(name = "bot", about = "Discord bot for TreeStats")]
+struct Cli {
+    /// TOML config file (defaults to bot.toml, if present)
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run the Discord bot and web server (the default)
+    Serve {
+        /// Only run the web viewer and API, without connecting to Discord's
+        /// gateway
+        #[arg(long, conflicts_with = "bot_only")]
+        web_only: bool,
+        /// Only run the Discord bot, without binding a port
+        #[arg(long)]
+        bot_only: bool,
+    },
+    /// Inspect or change the database schema
+    Migrate {
+        #[command(subcommand)]
+        action: MigrateAction,
+    },
+    /// Write a consistent copy of the SQLite database to a new file
+    Backup { path: PathBuf },
+}
+
+#[derive(Subcommand)]
+enum MigrateAction {
+    /// List migrations and whether they have been applied
+    Status,
+    /// Apply pending migrations (all of them unless a count is given)
+    Up { n: Option<usize> },
+    /// Revert the most recently applied migrations
+    Down {
+        #[arg(default_value_t = 1)]
+        n: usize,
+    },
+}
+
 async fn shutdown_signal() {
     use tokio::signal;
 
@@ -35,44 +99,172 @@ async fn shutdown_signal() {
     }
 }
 
+async fn migrate(config: &Config, action: MigrateAction) -> Result<(), Box<dyn Error>> {
+    let database = db::Database::connect(&config.database).await?;
+
+    match action {
+        MigrateAction::Status => {
+            println!("{:<16} {:<32} STATUS", "VERSION", "NAME");
+            for migration in database.migration_status().await? {
+                let mut status = match migration.applied_at {
+                    Some(ts) => format!(
+                        "applied {}",
+                        chrono::DateTime::from_timestamp(ts, 0)
+                            .map(|t| t.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+                            .unwrap_or_else(|| ts.to_string())
+                    ),
+                    None => "pending".to_string(),
+                };
+                if migration.modified {
+                    status.push_str(" (modified since applied)");
+                }
+                if migration.unknown {
+                    status.push_str(" (unknown to this build)");
+                }
+                if !migration.reversible {
+                    status.push_str(" (irreversible)");
+                }
+                println!("{:<16} {:<32} {status}", migration.version, migration.name);
+            }
+        }
+        MigrateAction::Up { n } => {
+            let applied = database.migrate_up(n).await?;
+            println!("Applied {applied} migration(s)");
+        }
+        MigrateAction::Down { n } => {
+            let reverted = database.migrate_down(n).await?;
+            println!("Reverted {reverted} migration(s)");
+        }
+    }
+
+    Ok(())
+}
+
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn Error>> {
+async fn main() -> Result<ExitCode, Box<dyn Error>> {
     env_logger::init();
 
-    let version = std::env::var("GIT_SHA_SHORT").unwrap_or_else(|_| "unknown".to_string());
-    let port = std::env::var("PORT").unwrap_or_else(|_| "3000".to_string());
-    let addr = format!("0.0.0.0:{port}");
-    let web_url =
-        std::env::var("WEB_URL").unwrap_or_else(|_| format!("http://localhost:{port}").to_string());
-    let token = std::env::var("DISCORD_BOT_TOKEN")
-        .map_err(|e| format!("Failed to get DISCORD_BOT_TOKEN: {e}"))?;
-
-    // Init db
-    let database = db::Database::init()
-        .await
-        .expect("Failed to initialize database");
-    info!("Database initialized successfully");
-
-    info!("Starting bot process (sha={version}) at {port} with WEB_URL={addr}...");
-    tokio::spawn(async move {
-        if let Err(e) = bot::start(token, web_url, database).await {
-            log::error!("bot::start failed: {e:?}");
-        }
+    let cli = Cli::parse();
+    let config = Config::load(cli.config.as_deref())?;
+
+    let command = cli.command.unwrap_or(Command::Serve {
+        web_only: false,
+        bot_only: false,
     });
 
-    let app = create_router();
-    let listener = tokio::net::TcpListener::bind(&addr)
-        .await
-        .expect("Failed to bind listener");
+    match command {
+        Command::Serve { web_only, bot_only } => serve(config, !bot_only, !web_only).await,
+        Command::Migrate { action } => {
+            migrate(&config, action).await?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Backup { path } => {
+            db::Database::connect(&config.database)
+                .await?
+                .backup(&path)
+                .await?;
+            println!("Backed up database to {}", path.display());
+            Ok(ExitCode::SUCCESS)
+        }
+    }
+}
+
+/// Where the web server gets captures from: fixture files if configured,
+/// otherwise Discord
+fn capture_source(config: &Config) -> Result<CaptureSource, Box<dyn Error>> {
+    if let Some(dir) = &config.fixtures_dir {
+        info!("Serving captures from fixtures in {}", dir.display());
+        return Ok(CaptureSource::Fixtures { dir: dir.clone() });
+    }
+
+    let token = config.discord_bot_token().map_err(|e| {
+        format!("{e:#}; set fixtures_dir (FIXTURES_DIR) to run the web server without Discord")
+    })?;
+    Ok(CaptureSource::Discord {
+        token: token.to_string(),
+    })
+}
+
+/// Run the Discord bot, the web server or both until a shutdown signal
+/// arrives or one of them stops
+async fn serve(config: Config, web: bool, bot: bool) -> Result<ExitCode, Box<dyn Error>> {
+    let version = &config.version;
+    let port = config.port;
+    let addr = format!("0.0.0.0:{port}");
+    let web_url = config.web_url();
+    metrics::init();
+
+    // Check everything needed is configured before starting anything
+    let token = if bot {
+        Some(config.discord_bot_token()?.to_string())
+    } else {
+        None
+    };
+    let source = if web {
+        Some(capture_source(&config)?)
+    } else {
+        None
+    };
+    let listener = match source {
+        Some(_) => Some(
+            tokio::net::TcpListener::bind(&addr)
+                .await
+                .map_err(|e| format!("Failed to bind {addr}: {e}"))?,
+        ),
+        None => None,
+    };
+
+    let mut health = health::Health::default();
+    let bot = match token {
+        Some(token) => {
+            // Init db
+            let database = db::Database::init(&config.database)
+                .await
+                .map_err(|e| format!("Failed to initialize database: {e:#}"))?;
+            info!("Database initialized successfully");
+
+            let mut tasks = supervisor::Tasks::new();
+            tasks.spawn(|shutdown| {
+                maintenance::run(database.clone(), config.maintenance.clone(), shutdown)
+            });
+            tasks.spawn(|shutdown| backup::run(database.clone(), config.backup.clone(), shutdown));
+
+            let (logs, log_writer) = log_queue::LogQueue::spawn(
+                std::sync::Arc::new(database.clone()),
+                config.log_queue.clone(),
+            );
+
+            info!("Starting bot process (sha={version}) with WEB_URL={web_url}...");
+            let handler = bot::Handler::new(
+                web_url,
+                logs.clone(),
+                std::sync::Arc::new(database.clone()),
+                std::sync::Arc::new(database.clone()),
+            );
+            let client = bot::client(token, handler).await?;
+            health = health::Health {
+                shard_manager: Some(client.shard_manager.clone()),
+                db: Some(database),
+            };
+            Some(supervisor::Bot::start(client, logs, log_writer, tasks))
+        }
+        None => None,
+    };
 
-    info!("Web server running on {addr}");
+    let web = match (source, listener) {
+        (Some(source), Some(listener)) => {
+            info!("Web server (sha={version}) running on {addr}");
+            Some(supervisor::Web::start(
+                listener,
+                create_router(source, health),
+            ))
+        }
+        _ => None,
+    };
 
-    axum::serve(listener, app)
-        .with_graceful_shutdown(shutdown_signal())
-        .await
-        .expect("Server failed");
+    let exit = supervisor::supervise(bot, web, shutdown_signal()).await;
 
     info!("Server shutdown complete");
 
-    Ok(())
+    Ok(exit.code())
 }
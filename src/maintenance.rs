@@ -0,0 +1,132 @@
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use serde::Deserialize;
+use tokio::sync::watch;
+use tracing::{error, info};
+
+use crate::db::{Database, SETTING_LAST_VACUUM};
+
+const DEFAULT_INTERVAL_HOURS: u64 = 24;
+const DEFAULT_VACUUM_INTERVAL_DAYS: u64 = 7;
+
+/// How long command logs are kept and how often the database is tidied
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MaintenanceConfig {
+    /// Days to keep command logs; 0 keeps them forever
+    pub log_retention_days: i64,
+    /// Whether purged logs are added to the daily rollup first
+    pub log_rollup: bool,
+    /// How often to purge and optimize
+    pub interval_hours: u64,
+    /// Minimum days between VACUUMs; 0 never vacuums
+    pub vacuum_interval_days: u64,
+}
+
+impl Default for MaintenanceConfig {
+    fn default() -> Self {
+        Self {
+            log_retention_days: 0,
+            log_rollup: true,
+            interval_hours: DEFAULT_INTERVAL_HOURS,
+            vacuum_interval_days: DEFAULT_VACUUM_INTERVAL_DAYS,
+        }
+    }
+}
+
+impl MaintenanceConfig {
+    fn retention_days(&self) -> Option<i64> {
+        (self.log_retention_days > 0).then_some(self.log_retention_days)
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval_hours * 3600)
+    }
+
+    fn vacuum_interval(&self) -> Option<Duration> {
+        (self.vacuum_interval_days > 0)
+            .then(|| Duration::from_secs(self.vacuum_interval_days * 86400))
+    }
+}
+
+/// Whether the last VACUUM was at least `every` ago
+///
+/// The time is kept in the database so restarts don't reset it. Without one
+/// the clock starts now, so a fresh deploy doesn't VACUUM straight away.
+async fn vacuum_due(db: &Database, every: Duration) -> Result<bool> {
+    let now = chrono::Utc::now().timestamp();
+    let last = db
+        .get_bot_setting(SETTING_LAST_VACUUM)
+        .await?
+        .and_then(|value| value.parse::<i64>().ok());
+
+    let Some(last) = last else {
+        db.set_bot_setting(SETTING_LAST_VACUUM, &now.to_string())
+            .await?;
+        return Ok(false);
+    };
+
+    Ok(now - last >= every.as_secs() as i64)
+}
+
+/// Purge expired command logs and let the database tidy up
+async fn run_once(db: &Database, config: &MaintenanceConfig) -> Result<()> {
+    if let Some(days) = config.retention_days() {
+        let before = chrono::Utc::now().timestamp() - days * 86400;
+        let report = db.purge_command_logs(before, config.log_rollup).await?;
+        if report.deleted > 0 {
+            info!(
+                "Purged {} command log(s) older than {} days ({} rolled up)",
+                report.deleted, days, report.rolled_up
+            );
+        }
+    }
+
+    db.optimize().await?;
+
+    if let Some(every) = config.vacuum_interval()
+        && vacuum_due(db, every).await?
+    {
+        let started = Instant::now();
+        db.vacuum().await?;
+        info!("Vacuumed database in {:?}", started.elapsed());
+        db.set_bot_setting(
+            SETTING_LAST_VACUUM,
+            &chrono::Utc::now().timestamp().to_string(),
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Run maintenance now and then on every interval until `shutdown` changes
+///
+/// A run in progress, which may be in the middle of a VACUUM, is finished
+/// before stopping.
+pub async fn run(db: Database, config: MaintenanceConfig, mut shutdown: watch::Receiver<bool>) {
+    info!(
+        "Database maintenance every {:?} (retention: {}, rollup: {}, vacuum every: {:?})",
+        config.interval(),
+        config
+            .retention_days()
+            .map_or("forever".to_string(), |days| format!("{days} days")),
+        config.log_rollup,
+        config.vacuum_interval()
+    );
+
+    let mut interval = tokio::time::interval(config.interval());
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = shutdown.changed() => break,
+        }
+
+        if let Err(e) = run_once(&db, &config).await {
+            error!("Database maintenance failed: {:#}", e);
+        }
+    }
+}
@@ -0,0 +1,268 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Mutex;
+
+use anyhow::Result;
+use serenity::async_trait;
+
+use crate::db::{
+    Alias, CommandLog, DailyUsage, GuildSettings, RecentLog, ServerQuery, UnmatchedQuery, UserLog,
+    UserStats,
+};
+use crate::store::{CommandLogStore, ServerStore, SettingsStore};
+
+/// Everything the bot stores, kept in memory so bot logic can be tested
+/// without a database
+#[derive(Default)]
+pub struct MemoryStore {
+    logs: Mutex<Vec<(i64, CommandLog)>>,
+    opted_out: Mutex<HashSet<String>>,
+    /// Aliases and who created them
+    aliases: Mutex<Vec<(Alias, Option<String>)>>,
+    server_queries: Mutex<Vec<ServerQuery>>,
+    guild_settings: Mutex<HashMap<String, GuildSettings>>,
+    bot_settings: Mutex<HashMap<String, String>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// Count successful logs per key, most common first
+fn count_by<'a>(
+    logs: impl Iterator<Item = &'a (i64, CommandLog)>,
+    key: impl Fn(&(i64, CommandLog)) -> String,
+) -> Vec<(String, i64)> {
+    let mut counts: HashMap<String, i64> = HashMap::new();
+    for entry in logs.filter(|(_, log)| log.success) {
+        *counts.entry(key(entry)).or_default() += 1;
+    }
+
+    let mut counts: Vec<(String, i64)> = counts.into_iter().collect();
+    counts.sort_by(|(a_name, a), (b_name, b)| b.cmp(a).then(a_name.cmp(b_name)));
+    counts
+}
+
+#[async_trait]
+impl CommandLogStore for MemoryStore {
+    async fn log_command(&self, mut log: CommandLog) -> Result<()> {
+        if self.opted_out.lock().unwrap().contains(&log.user_id) {
+            log.anonymize();
+        }
+
+        self.logs
+            .lock()
+            .unwrap()
+            .push((chrono::Utc::now().timestamp(), log));
+
+        Ok(())
+    }
+
+    async fn get_command_stats(&self) -> Result<Vec<(String, i64)>> {
+        let logs = self.logs.lock().unwrap();
+        Ok(count_by(logs.iter(), |(_, log)| log.command_name.clone()))
+    }
+
+    async fn get_recent_logs(&self, limit: i64) -> Result<Vec<RecentLog>> {
+        let logs = self.logs.lock().unwrap();
+        Ok(logs
+            .iter()
+            .rev()
+            .take(limit.max(0) as usize)
+            .map(|(timestamp, log)| RecentLog {
+                command_name: log.command_name.clone(),
+                user_name: log.user_name.clone(),
+                timestamp: *timestamp,
+                success: log.success,
+            })
+            .collect())
+    }
+
+    async fn get_total_uses(&self) -> Result<i64> {
+        let logs = self.logs.lock().unwrap();
+        Ok(logs.iter().filter(|(_, log)| log.success).count() as i64)
+    }
+
+    async fn get_user_stats(&self, user_id: &str) -> Result<UserStats> {
+        let logs = self.logs.lock().unwrap();
+        let user_logs = || logs.iter().filter(|(_, log)| log.user_id == user_id);
+
+        let command_breakdown = count_by(user_logs(), |(_, log)| log.command_name.clone());
+
+        Ok(UserStats {
+            user_id: user_id.to_string(),
+            total_count: command_breakdown.iter().map(|(_, count)| count).sum(),
+            command_breakdown,
+            first_use: user_logs().map(|(timestamp, _)| *timestamp).min(),
+            last_use: user_logs().map(|(timestamp, _)| *timestamp).max(),
+        })
+    }
+
+    async fn get_usage_over_time(&self, days: i64) -> Result<Vec<DailyUsage>> {
+        let (cutoff, _) = crate::db::usage_cutoff(days);
+        let logs = self.logs.lock().unwrap();
+
+        let mut by_date: BTreeMap<String, i64> = BTreeMap::new();
+        for (timestamp, _) in logs
+            .iter()
+            .filter(|(timestamp, log)| log.success && *timestamp >= cutoff)
+        {
+            let date = chrono::DateTime::from_timestamp(*timestamp, 0)
+                .unwrap_or_default()
+                .format("%Y-%m-%d")
+                .to_string();
+            *by_date.entry(date).or_default() += 1;
+        }
+
+        Ok(by_date
+            .into_iter()
+            .rev()
+            .map(|(date, count)| DailyUsage { date, count })
+            .collect())
+    }
+
+    async fn get_user_logs(&self, user_id: &str) -> Result<Vec<UserLog>> {
+        let logs = self.logs.lock().unwrap();
+        Ok(logs
+            .iter()
+            .filter(|(_, log)| log.user_id == user_id)
+            .map(|(timestamp, log)| UserLog {
+                command_name: log.command_name.clone(),
+                user_id: log.user_id.clone(),
+                user_name: log.user_name.clone(),
+                channel_id: log.channel_id.clone(),
+                guild_id: log.guild_id.clone(),
+                timestamp: *timestamp,
+                message_id: log.message_id.clone(),
+                success: log.success,
+                error_message: log.error_message.clone(),
+                options: log.options.clone(),
+                latency_ms: log.latency_ms,
+                detail: log.detail.clone(),
+            })
+            .collect())
+    }
+
+    async fn delete_user_data(&self, user_id: &str) -> Result<u64> {
+        let mut logs = self.logs.lock().unwrap();
+        let before = logs.len();
+        logs.retain(|(_, log)| log.user_id != user_id);
+
+        self.opted_out.lock().unwrap().insert(user_id.to_string());
+        for (_, created_by) in self.aliases.lock().unwrap().iter_mut() {
+            if created_by.as_deref() == Some(user_id) {
+                *created_by = None;
+            }
+        }
+
+        Ok((before - logs.len()) as u64)
+    }
+}
+
+#[async_trait]
+impl ServerStore for MemoryStore {
+    async fn get_aliases(&self, guild_id: Option<&str>) -> Result<Vec<Alias>> {
+        let mut aliases: Vec<Alias> = self
+            .aliases
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(alias, _)| alias)
+            .filter(|alias| alias.guild_id.is_none() || alias.guild_id.as_deref() == guild_id)
+            .cloned()
+            .collect();
+        aliases.sort_by(|a, b| {
+            (a.guild_id.is_none(), &a.alias).cmp(&(b.guild_id.is_none(), &b.alias))
+        });
+        Ok(aliases)
+    }
+
+    async fn add_alias(&self, alias: &Alias, created_by: &str) -> Result<()> {
+        self.remove_alias(&alias.alias, alias.guild_id.as_deref())
+            .await?;
+
+        let created_by =
+            (!self.opted_out.lock().unwrap().contains(created_by)).then(|| created_by.to_string());
+        self.aliases
+            .lock()
+            .unwrap()
+            .push((alias.clone(), created_by));
+
+        Ok(())
+    }
+
+    async fn remove_alias(&self, alias: &str, guild_id: Option<&str>) -> Result<bool> {
+        let mut aliases = self.aliases.lock().unwrap();
+        let before = aliases.len();
+        aliases.retain(|(a, _)| !(a.alias == alias && a.guild_id.as_deref() == guild_id));
+        Ok(aliases.len() < before)
+    }
+
+    async fn log_server_query(&self, query: ServerQuery) -> Result<()> {
+        self.server_queries.lock().unwrap().push(query);
+        Ok(())
+    }
+
+    async fn get_unmatched_queries(&self, limit: i64) -> Result<Vec<UnmatchedQuery>> {
+        let queries = self.server_queries.lock().unwrap();
+
+        // The newest, best-scoring lookup stands for each query
+        let mut unmatched: Vec<UnmatchedQuery> = Vec::new();
+        for query in queries.iter().rev().filter(|q| q.resolved_name.is_none()) {
+            let key = query.query.trim().to_lowercase();
+            match unmatched.iter_mut().find(|u| u.query == key) {
+                Some(entry) => {
+                    entry.count += 1;
+                    if query.score > entry.score {
+                        entry.closest_name = query.closest_name.clone();
+                        entry.score = query.score;
+                    }
+                }
+                None => unmatched.push(UnmatchedQuery {
+                    query: key,
+                    count: 1,
+                    closest_name: query.closest_name.clone(),
+                    score: query.score,
+                }),
+            }
+        }
+
+        unmatched.sort_by(|a, b| b.count.cmp(&a.count).then(a.query.cmp(&b.query)));
+        unmatched.truncate(limit.max(0) as usize);
+        Ok(unmatched)
+    }
+}
+
+#[async_trait]
+impl SettingsStore for MemoryStore {
+    async fn get_guild_settings(&self, guild_id: &str) -> Result<GuildSettings> {
+        Ok(self
+            .guild_settings
+            .lock()
+            .unwrap()
+            .get(guild_id)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    async fn save_guild_settings(&self, guild_id: &str, settings: &GuildSettings) -> Result<()> {
+        self.guild_settings
+            .lock()
+            .unwrap()
+            .insert(guild_id.to_string(), settings.clone());
+        Ok(())
+    }
+
+    async fn get_bot_setting(&self, key: &str) -> Result<Option<String>> {
+        Ok(self.bot_settings.lock().unwrap().get(key).cloned())
+    }
+
+    async fn set_bot_setting(&self, key: &str, value: &str) -> Result<()> {
+        self.bot_settings
+            .lock()
+            .unwrap()
+            .insert(key.to_string(), value.to_string());
+        Ok(())
+    }
+}
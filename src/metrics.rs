@@ -0,0 +1,124 @@
+use std::sync::LazyLock;
+
+use prometheus::{
+    Encoder, GaugeVec, Histogram, HistogramVec, IntCounter, IntCounterVec, TextEncoder,
+    register_gauge_vec, register_histogram, register_histogram_vec, register_int_counter,
+    register_int_counter_vec,
+};
+use serenity::gateway::ShardManager;
+
+// Registered with the default registry by `init`, or else the first time
+// they're used
+
+/// Slash commands by command name and outcome (`success` or `error`)
+pub static SLASH_COMMANDS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "bot_slash_commands_total",
+        "Slash commands handled",
+        &["command", "outcome"]
+    )
+    .unwrap()
+});
+
+/// PCAP attachments the bot replied to
+pub static PCAP_DETECTIONS: LazyLock<IntCounter> = LazyLock::new(|| {
+    register_int_counter!("bot_pcap_detections_total", "PCAP attachments detected").unwrap()
+});
+
+pub static ATTACHMENT_BYTES: LazyLock<IntCounter> = LazyLock::new(|| {
+    register_int_counter!(
+        "bot_attachment_download_bytes_total",
+        "Bytes of attachments downloaded from Discord"
+    )
+    .unwrap()
+});
+
+pub static ATTACHMENT_DURATION: LazyLock<Histogram> = LazyLock::new(|| {
+    register_histogram!(
+        "bot_attachment_download_duration_seconds",
+        "Time taken to download an attachment from Discord"
+    )
+    .unwrap()
+});
+
+/// Responses to message fetches by HTTP status, or `error` if there was none
+pub static DISCORD_API_RESPONSES: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "bot_discord_api_responses_total",
+        "Discord API responses when fetching messages",
+        &["status"]
+    )
+    .unwrap()
+});
+
+pub static SERVERS_FETCH_FAILURES: LazyLock<IntCounter> = LazyLock::new(|| {
+    register_int_counter!(
+        "bot_servers_fetch_failures_total",
+        "Failed fetches of servers.json"
+    )
+    .unwrap()
+});
+
+/// Web requests by method, route pattern and response status
+pub static HTTP_REQUEST_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "bot_http_request_duration_seconds",
+        "Time taken to handle web requests",
+        &["method", "route", "status"]
+    )
+    .unwrap()
+});
+
+pub static COMMAND_LOGS_DROPPED: LazyLock<IntCounter> = LazyLock::new(|| {
+    register_int_counter!(
+        "bot_command_logs_dropped_total",
+        "Command logs dropped because the write queue was full or they couldn't be written"
+    )
+    .unwrap()
+});
+
+static GATEWAY_LATENCY: LazyLock<GaugeVec> = LazyLock::new(|| {
+    register_gauge_vec!(
+        "bot_gateway_latency_seconds",
+        "Latest gateway heartbeat latency by shard",
+        &["shard"]
+    )
+    .unwrap()
+});
+
+/// Register every metric, so counters show up as 0 before anything happens
+pub fn init() {
+    LazyLock::force(&SLASH_COMMANDS);
+    LazyLock::force(&PCAP_DETECTIONS);
+    LazyLock::force(&ATTACHMENT_BYTES);
+    LazyLock::force(&ATTACHMENT_DURATION);
+    LazyLock::force(&DISCORD_API_RESPONSES);
+    LazyLock::force(&SERVERS_FETCH_FAILURES);
+    LazyLock::force(&HTTP_REQUEST_DURATION);
+    LazyLock::force(&COMMAND_LOGS_DROPPED);
+    LazyLock::force(&GATEWAY_LATENCY);
+}
+
+/// Render every metric in Prometheus' text format
+///
+/// Gateway latency is read from `shard_manager` now rather than tracked as
+/// it changes.
+pub async fn render(shard_manager: Option<&ShardManager>) -> String {
+    GATEWAY_LATENCY.reset();
+    if let Some(shard_manager) = shard_manager {
+        for (id, runner) in shard_manager.runners.lock().await.iter() {
+            if let Some(latency) = runner.latency {
+                GATEWAY_LATENCY
+                    .with_label_values(&[id.0.to_string()])
+                    .set(latency.as_secs_f64());
+            }
+        }
+    }
+
+    let mut buffer = Vec::new();
+    if let Err(e) = TextEncoder::new().encode(&prometheus::gather(), &mut buffer) {
+        tracing::error!("Failed to encode metrics: {}", e);
+    }
+
+    String::from_utf8(buffer).unwrap_or_default()
+}
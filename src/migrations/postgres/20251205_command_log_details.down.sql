@@ -0,0 +1,3 @@
+ALTER TABLE command_logs DROP COLUMN detail;
+ALTER TABLE command_logs DROP COLUMN latency_ms;
+ALTER TABLE command_logs DROP COLUMN options;
@@ -0,0 +1,6 @@
+-- Record options, latency and command-specific detail (such as the server a
+-- /server query resolved to) for every logged command
+
+ALTER TABLE command_logs ADD COLUMN options TEXT;
+ALTER TABLE command_logs ADD COLUMN latency_ms BIGINT;
+ALTER TABLE command_logs ADD COLUMN detail TEXT;
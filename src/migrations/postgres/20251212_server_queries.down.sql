@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS idx_server_queries_resolved_name;
+DROP TABLE IF EXISTS server_queries;
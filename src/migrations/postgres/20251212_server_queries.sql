@@ -0,0 +1,14 @@
+-- Outcome of every /server lookup, used to find queries fuzzy matching misses
+
+CREATE TABLE IF NOT EXISTS server_queries (
+    id BIGSERIAL PRIMARY KEY,
+    query TEXT NOT NULL,
+    resolved_name TEXT,
+    closest_name TEXT,
+    score DOUBLE PRECISION,
+    guild_id TEXT,
+    timestamp BIGINT NOT NULL DEFAULT (EXTRACT(EPOCH FROM now())::BIGINT)
+);
+
+-- Index for reporting unmatched queries
+CREATE INDEX IF NOT EXISTS idx_server_queries_resolved_name ON server_queries(resolved_name);
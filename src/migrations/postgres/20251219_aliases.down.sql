@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS idx_aliases_alias_guild_id;
+DROP TABLE IF EXISTS aliases;
@@ -0,0 +1,11 @@
+-- Per-guild settings managed with /config; a missing row means all defaults
+
+CREATE TABLE IF NOT EXISTS guild_settings (
+    guild_id TEXT PRIMARY KEY,
+    -- Comma-separated channel IDs PCAP detection is limited to; empty means all
+    pcap_channels TEXT NOT NULL DEFAULT '',
+    default_server TEXT,
+    reply_style TEXT NOT NULL DEFAULT 'text',
+    auto_summarize BOOLEAN NOT NULL DEFAULT FALSE,
+    updated_at BIGINT NOT NULL DEFAULT (EXTRACT(EPOCH FROM now())::BIGINT)
+);
@@ -0,0 +1 @@
+DROP TABLE IF EXISTS privacy_opt_outs;
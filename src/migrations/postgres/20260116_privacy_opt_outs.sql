@@ -0,0 +1,6 @@
+-- Users who deleted their data; their future commands are logged anonymously
+
+CREATE TABLE IF NOT EXISTS privacy_opt_outs (
+    user_id TEXT PRIMARY KEY,
+    opted_out_at BIGINT NOT NULL DEFAULT (EXTRACT(EPOCH FROM now())::BIGINT)
+);
@@ -0,0 +1,5 @@
+UPDATE command_logs SET message_id = '' WHERE message_id IS NULL;
+ALTER TABLE command_logs ALTER COLUMN message_id SET NOT NULL;
+
+UPDATE aliases SET created_by = '' WHERE created_by IS NULL;
+ALTER TABLE aliases ALTER COLUMN created_by SET NOT NULL;
@@ -0,0 +1,10 @@
+-- Anonymized command logs no longer keep the message they came from, and
+-- aliases no longer keep who created them once that user opts out, so both
+-- columns become nullable
+
+ALTER TABLE command_logs ALTER COLUMN message_id DROP NOT NULL;
+ALTER TABLE aliases ALTER COLUMN created_by DROP NOT NULL;
+
+-- Scrub what's already there
+UPDATE command_logs SET message_id = NULL, options = NULL WHERE user_id = '0';
+UPDATE aliases SET created_by = NULL WHERE created_by IN (SELECT user_id FROM privacy_opt_outs);
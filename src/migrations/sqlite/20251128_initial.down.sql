@@ -0,0 +1,6 @@
+-- Revert the initial schema
+
+DROP INDEX IF EXISTS idx_command_logs_timestamp;
+DROP INDEX IF EXISTS idx_command_logs_user_id;
+DROP INDEX IF EXISTS idx_command_logs_command_name;
+DROP TABLE IF EXISTS command_logs;
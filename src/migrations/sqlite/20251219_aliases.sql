@@ -0,0 +1,13 @@
+-- Admin-managed nicknames for servers, per guild or global (NULL guild_id)
+
+CREATE TABLE IF NOT EXISTS aliases (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    alias TEXT NOT NULL,
+    server_name TEXT NOT NULL,
+    guild_id TEXT,
+    created_by TEXT NOT NULL,
+    created_at INTEGER NOT NULL DEFAULT (unixepoch())
+);
+
+-- One alias per name in each scope
+CREATE UNIQUE INDEX IF NOT EXISTS idx_aliases_alias_guild_id ON aliases(alias, COALESCE(guild_id, ''));
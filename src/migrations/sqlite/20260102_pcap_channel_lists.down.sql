@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS bot_settings;
+ALTER TABLE guild_settings DROP COLUMN pcap_denied_channels;
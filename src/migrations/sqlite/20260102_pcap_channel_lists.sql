@@ -0,0 +1,10 @@
+-- Channels PCAP detection never runs in, alongside the existing allowlist
+
+ALTER TABLE guild_settings ADD COLUMN pcap_denied_channels TEXT NOT NULL DEFAULT '';
+
+-- Settings that apply to the whole bot rather than one guild
+CREATE TABLE IF NOT EXISTS bot_settings (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL,
+    updated_at INTEGER NOT NULL DEFAULT (unixepoch())
+);
@@ -0,0 +1,9 @@
+-- Daily per-command totals for command logs that have been purged
+
+CREATE TABLE IF NOT EXISTS command_usage_daily (
+    date TEXT NOT NULL,
+    command_name TEXT NOT NULL,
+    success_count INTEGER NOT NULL DEFAULT 0,
+    failure_count INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (date, command_name)
+);
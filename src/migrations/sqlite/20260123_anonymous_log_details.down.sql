@@ -0,0 +1,44 @@
+CREATE TABLE command_logs_old (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    command_name TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    user_name TEXT NOT NULL,
+    channel_id TEXT NOT NULL,
+    guild_id TEXT,
+    timestamp INTEGER NOT NULL DEFAULT (unixepoch()),
+    message_id TEXT NOT NULL,
+    success BOOLEAN NOT NULL DEFAULT 1,
+    error_message TEXT,
+    options TEXT,
+    latency_ms INTEGER,
+    detail TEXT
+);
+
+INSERT INTO command_logs_old
+SELECT id, command_name, user_id, user_name, channel_id, guild_id, timestamp, COALESCE(message_id, ''), success, error_message, options, latency_ms, detail
+FROM command_logs;
+
+DROP TABLE command_logs;
+ALTER TABLE command_logs_old RENAME TO command_logs;
+
+CREATE INDEX IF NOT EXISTS idx_command_logs_command_name ON command_logs(command_name);
+CREATE INDEX IF NOT EXISTS idx_command_logs_user_id ON command_logs(user_id);
+CREATE INDEX IF NOT EXISTS idx_command_logs_timestamp ON command_logs(timestamp DESC);
+
+CREATE TABLE aliases_old (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    alias TEXT NOT NULL,
+    server_name TEXT NOT NULL,
+    guild_id TEXT,
+    created_by TEXT NOT NULL,
+    created_at INTEGER NOT NULL DEFAULT (unixepoch())
+);
+
+INSERT INTO aliases_old
+SELECT id, alias, server_name, guild_id, COALESCE(created_by, ''), created_at
+FROM aliases;
+
+DROP TABLE aliases;
+ALTER TABLE aliases_old RENAME TO aliases;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_aliases_alias_guild_id ON aliases(alias, COALESCE(guild_id, ''));
@@ -0,0 +1,52 @@
+-- Anonymized command logs no longer keep the message they came from, and
+-- aliases no longer keep who created them once that user opts out, so both
+-- columns become nullable
+
+CREATE TABLE command_logs_new (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    command_name TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    user_name TEXT NOT NULL,
+    channel_id TEXT NOT NULL,
+    guild_id TEXT,
+    timestamp INTEGER NOT NULL DEFAULT (unixepoch()),
+    message_id TEXT,
+    success BOOLEAN NOT NULL DEFAULT 1,
+    error_message TEXT,
+    options TEXT,
+    latency_ms INTEGER,
+    detail TEXT
+);
+
+INSERT INTO command_logs_new
+SELECT id, command_name, user_id, user_name, channel_id, guild_id, timestamp, message_id, success, error_message, options, latency_ms, detail
+FROM command_logs;
+
+DROP TABLE command_logs;
+ALTER TABLE command_logs_new RENAME TO command_logs;
+
+CREATE INDEX IF NOT EXISTS idx_command_logs_command_name ON command_logs(command_name);
+CREATE INDEX IF NOT EXISTS idx_command_logs_user_id ON command_logs(user_id);
+CREATE INDEX IF NOT EXISTS idx_command_logs_timestamp ON command_logs(timestamp DESC);
+
+CREATE TABLE aliases_new (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    alias TEXT NOT NULL,
+    server_name TEXT NOT NULL,
+    guild_id TEXT,
+    created_by TEXT,
+    created_at INTEGER NOT NULL DEFAULT (unixepoch())
+);
+
+INSERT INTO aliases_new
+SELECT id, alias, server_name, guild_id, created_by, created_at
+FROM aliases;
+
+DROP TABLE aliases;
+ALTER TABLE aliases_new RENAME TO aliases;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_aliases_alias_guild_id ON aliases(alias, COALESCE(guild_id, ''));
+
+-- Scrub what's already there
+UPDATE command_logs SET message_id = NULL, options = NULL WHERE user_id = '0';
+UPDATE aliases SET created_by = NULL WHERE created_by IN (SELECT user_id FROM privacy_opt_outs);
@@ -0,0 +1,545 @@
+use anyhow::{Result, bail};
+
+/// Link-layer header types we know how to decode
+pub const LINKTYPE_NULL: u32 = 0;
+pub const LINKTYPE_ETHERNET: u32 = 1;
+pub const LINKTYPE_RAW: u32 = 101;
+pub const LINKTYPE_LINUX_SLL: u32 = 113;
+pub const LINKTYPE_IPV4: u32 = 228;
+pub const LINKTYPE_IPV6: u32 = 229;
+
+const PCAP_SNAPLEN: u32 = 262_144;
+const PCAP_MAGIC_MICROS: u32 = 0xa1b2_c3d4;
+const PCAP_MAGIC_NANOS: u32 = 0xa1b2_3c4d;
+const PCAPNG_SECTION_HEADER: u32 = 0x0a0d_0d0a;
+const PCAPNG_BYTE_ORDER_MAGIC: u32 = 0x1a2b_3c4d;
+const PCAPNG_INTERFACE_DESCRIPTION: u32 = 0x0000_0001;
+const PCAPNG_SIMPLE_PACKET: u32 = 0x0000_0003;
+const PCAPNG_ENHANCED_PACKET: u32 = 0x0000_0006;
+const PCAPNG_OPTION_TSRESOL: u16 = 9;
+
+/// A parsed capture file borrowing from the original bytes
+#[derive(Debug)]
+pub struct Capture<'a> {
+    pub packets: Vec<Packet<'a>>,
+}
+
+#[derive(Debug)]
+pub struct Packet<'a> {
+    pub link_type: u32,
+    /// Microseconds since the Unix epoch
+    pub timestamp_us: i64,
+    /// Length of the packet on the wire (may exceed `data.len()`)
+    pub orig_len: u32,
+    /// Offset of `data` within the capture file
+    pub offset: usize,
+    pub data: &'a [u8],
+}
+
+/// Bounds-checked reads with a fixed byte order
+#[derive(Clone, Copy)]
+struct Endian {
+    big: bool,
+}
+
+impl Endian {
+    fn u16(self, bytes: &[u8], offset: usize) -> Option<u16> {
+        let raw: [u8; 2] = bytes.get(offset..offset + 2)?.try_into().ok()?;
+        Some(if self.big {
+            u16::from_be_bytes(raw)
+        } else {
+            u16::from_le_bytes(raw)
+        })
+    }
+
+    fn u32(self, bytes: &[u8], offset: usize) -> Option<u32> {
+        let raw: [u8; 4] = bytes.get(offset..offset + 4)?.try_into().ok()?;
+        Some(if self.big {
+            u32::from_be_bytes(raw)
+        } else {
+            u32::from_le_bytes(raw)
+        })
+    }
+}
+
+/// Parse a classic pcap or pcapng capture
+pub fn parse(bytes: &[u8]) -> Result<Capture<'_>> {
+    let Some(magic) = bytes.get(0..4) else {
+        bail!("File is too short to be a capture");
+    };
+    let magic = u32::from_le_bytes(magic.try_into()?);
+
+    match magic {
+        PCAPNG_SECTION_HEADER => parse_pcapng(bytes),
+        m if m == PCAP_MAGIC_MICROS
+            || m == PCAP_MAGIC_NANOS
+            || m.swap_bytes() == PCAP_MAGIC_MICROS
+            || m.swap_bytes() == PCAP_MAGIC_NANOS =>
+        {
+            parse_pcap(bytes)
+        }
+        _ => bail!("Unrecognized capture format (magic {magic:#010x})"),
+    }
+}
+
+fn parse_pcap(bytes: &[u8]) -> Result<Capture<'_>> {
+    let le = u32::from_le_bytes(bytes[0..4].try_into()?);
+    let endian = Endian {
+        big: le != PCAP_MAGIC_MICROS && le != PCAP_MAGIC_NANOS,
+    };
+    let magic = endian.u32(bytes, 0).unwrap_or_default();
+    let nanos = magic == PCAP_MAGIC_NANOS;
+
+    let Some(link_type) = endian.u32(bytes, 20) else {
+        bail!("Truncated pcap file header");
+    };
+
+    let mut packets = Vec::new();
+    let mut offset = 24;
+
+    while offset < bytes.len() {
+        let (Some(ts_sec), Some(ts_frac), Some(incl_len), Some(orig_len)) = (
+            endian.u32(bytes, offset),
+            endian.u32(bytes, offset + 4),
+            endian.u32(bytes, offset + 8),
+            endian.u32(bytes, offset + 12),
+        ) else {
+            // Trailing garbage or a truncated record header; keep what we have
+            break;
+        };
+
+        let start = offset + 16;
+        let Some(data) = bytes.get(start..start + incl_len as usize) else {
+            break;
+        };
+
+        let frac_us = if nanos { ts_frac / 1000 } else { ts_frac };
+
+        packets.push(Packet {
+            link_type,
+            timestamp_us: ts_sec as i64 * 1_000_000 + frac_us as i64,
+            orig_len,
+            offset: start,
+            data,
+        });
+
+        offset = start + incl_len as usize;
+    }
+
+    Ok(Capture { packets })
+}
+
+/// Per-interface state from a pcapng Interface Description Block
+struct Interface {
+    link_type: u32,
+    /// Timestamp resolution: (is power of two, exponent)
+    tsresol: (bool, u8),
+}
+
+impl Interface {
+    fn to_micros(&self, ts: u64) -> i64 {
+        let (binary, exp) = self.tsresol;
+        let micros = if binary {
+            (ts as u128 * 1_000_000)
+                .checked_shr(exp as u32)
+                .unwrap_or(0)
+        } else if exp >= 6 {
+            10u128
+                .checked_pow((exp - 6) as u32)
+                .map_or(0, |divisor| ts as u128 / divisor)
+        } else {
+            ts as u128 * 10u128.pow((6 - exp) as u32)
+        };
+        micros as i64
+    }
+}
+
+fn parse_pcapng(bytes: &[u8]) -> Result<Capture<'_>> {
+    let mut packets = Vec::new();
+    let mut interfaces: Vec<Interface> = Vec::new();
+    let mut endian = Endian { big: false };
+    let mut offset = 0;
+
+    while offset + 12 <= bytes.len() {
+        // The section header determines byte order for everything after it
+        let raw_type = u32::from_le_bytes(bytes[offset..offset + 4].try_into()?);
+        if raw_type == PCAPNG_SECTION_HEADER {
+            let Some(bom) = bytes.get(offset + 8..offset + 12) else {
+                break;
+            };
+            endian.big = u32::from_le_bytes(bom.try_into()?) != PCAPNG_BYTE_ORDER_MAGIC;
+            interfaces.clear();
+        }
+
+        let (Some(block_type), Some(block_len)) =
+            (endian.u32(bytes, offset), endian.u32(bytes, offset + 4))
+        else {
+            break;
+        };
+        let block_len = block_len as usize;
+        if block_len < 12 || offset + block_len > bytes.len() {
+            break;
+        }
+
+        let body = &bytes[offset + 8..offset + block_len - 4];
+
+        match block_type {
+            PCAPNG_INTERFACE_DESCRIPTION => {
+                let link_type = endian.u16(body, 0).unwrap_or_default() as u32;
+                let mut tsresol = (false, 6);
+
+                // Options follow the fixed 8-byte body
+                let mut opt = 8;
+                while let (Some(code), Some(len)) =
+                    (endian.u16(body, opt), endian.u16(body, opt + 2))
+                {
+                    if code == 0 {
+                        break;
+                    }
+                    if code == PCAPNG_OPTION_TSRESOL
+                        && let Some(&value) = body.get(opt + 4)
+                    {
+                        tsresol = (value & 0x80 != 0, value & 0x7f);
+                        // Units finer than 2^-64 or 10^-38 s can't be converted
+                        let supported = if tsresol.0 {
+                            tsresol.1 <= 64
+                        } else {
+                            10u128.checked_pow(tsresol.1 as u32).is_some()
+                        };
+                        if !supported {
+                            bail!(
+                                "Unsupported timestamp resolution {value:#04x} at offset {offset}"
+                            );
+                        }
+                    }
+                    opt += 4 + (len as usize).div_ceil(4) * 4;
+                }
+
+                interfaces.push(Interface { link_type, tsresol });
+            }
+            PCAPNG_ENHANCED_PACKET => {
+                let (Some(iface), Some(ts_high), Some(ts_low), Some(cap_len), Some(orig_len)) = (
+                    endian.u32(body, 0),
+                    endian.u32(body, 4),
+                    endian.u32(body, 8),
+                    endian.u32(body, 12),
+                    endian.u32(body, 16),
+                ) else {
+                    bail!("Truncated enhanced packet block at offset {offset}");
+                };
+                let Some(interface) = interfaces.get(iface as usize) else {
+                    bail!("Packet references unknown interface {iface}");
+                };
+                let Some(data) = body.get(20..20 + cap_len as usize) else {
+                    bail!("Truncated packet data at offset {offset}");
+                };
+
+                packets.push(Packet {
+                    link_type: interface.link_type,
+                    timestamp_us: interface.to_micros(((ts_high as u64) << 32) | ts_low as u64),
+                    orig_len,
+                    offset: offset + 8 + 20,
+                    data,
+                });
+            }
+            PCAPNG_SIMPLE_PACKET => {
+                let Some(interface) = interfaces.first() else {
+                    bail!("Simple packet block without an interface");
+                };
+                let orig_len = endian.u32(body, 0).unwrap_or_default();
+                let data = &body[4.min(body.len())..];
+                let data = &data[..data.len().min(orig_len as usize)];
+
+                packets.push(Packet {
+                    link_type: interface.link_type,
+                    timestamp_us: 0,
+                    orig_len,
+                    offset: offset + 8 + 4,
+                    data,
+                });
+            }
+            _ => {}
+        }
+
+        offset += block_len;
+    }
+
+    Ok(Capture { packets })
+}
+
+/// Write packets out as a classic little-endian, microsecond pcap file
+///
+/// Classic pcap has a single link type per file, so every packet must share
+/// the link type of the first one.
+pub fn write<'a>(packets: impl IntoIterator<Item = &'a Packet<'a>>) -> Result<Vec<u8>> {
+    let mut packets = packets.into_iter().peekable();
+    let link_type = packets
+        .peek()
+        .map(|p| p.link_type)
+        .unwrap_or(LINKTYPE_ETHERNET);
+
+    let mut out = Vec::new();
+    out.extend_from_slice(&PCAP_MAGIC_MICROS.to_le_bytes());
+    out.extend_from_slice(&2u16.to_le_bytes());
+    out.extend_from_slice(&4u16.to_le_bytes());
+    out.extend_from_slice(&0i32.to_le_bytes());
+    out.extend_from_slice(&0u32.to_le_bytes());
+    out.extend_from_slice(&PCAP_SNAPLEN.to_le_bytes());
+    out.extend_from_slice(&link_type.to_le_bytes());
+
+    for packet in packets {
+        if packet.link_type != link_type {
+            bail!(
+                "Cannot mix link types {link_type} and {} in one pcap file",
+                packet.link_type
+            );
+        }
+
+        let ts_sec = packet.timestamp_us.div_euclid(1_000_000) as u32;
+        let ts_usec = packet.timestamp_us.rem_euclid(1_000_000) as u32;
+
+        out.extend_from_slice(&ts_sec.to_le_bytes());
+        out.extend_from_slice(&ts_usec.to_le_bytes());
+        out.extend_from_slice(&(packet.data.len() as u32).to_le_bytes());
+        out.extend_from_slice(&packet.orig_len.to_le_bytes());
+        out.extend_from_slice(packet.data);
+    }
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A pcapng block: type, length, body (padded to 4 bytes), length
+    fn block(block_type: u32, body: &[u8]) -> Vec<u8> {
+        let padded = body.len().div_ceil(4) * 4;
+        let len = (12 + padded) as u32;
+
+        let mut out = Vec::new();
+        out.extend_from_slice(&block_type.to_le_bytes());
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(body);
+        out.resize(8 + padded, 0);
+        out.extend_from_slice(&len.to_le_bytes());
+        out
+    }
+
+    fn section_header() -> Vec<u8> {
+        let mut body = Vec::new();
+        body.extend_from_slice(&PCAPNG_BYTE_ORDER_MAGIC.to_le_bytes());
+        body.extend_from_slice(&1u16.to_le_bytes());
+        body.extend_from_slice(&0u16.to_le_bytes());
+        body.extend_from_slice(&(-1i64).to_le_bytes());
+        block(PCAPNG_SECTION_HEADER, &body)
+    }
+
+    /// An interface description block, with `if_tsresol` if given
+    fn interface(link_type: u16, tsresol: Option<u8>) -> Vec<u8> {
+        let mut body = Vec::new();
+        body.extend_from_slice(&link_type.to_le_bytes());
+        body.extend_from_slice(&0u16.to_le_bytes());
+        body.extend_from_slice(&PCAP_SNAPLEN.to_le_bytes());
+        if let Some(tsresol) = tsresol {
+            body.extend_from_slice(&PCAPNG_OPTION_TSRESOL.to_le_bytes());
+            body.extend_from_slice(&1u16.to_le_bytes());
+            body.extend_from_slice(&[tsresol, 0, 0, 0]);
+        }
+        body.extend_from_slice(&[0; 4]);
+        block(PCAPNG_INTERFACE_DESCRIPTION, &body)
+    }
+
+    fn enhanced_packet(iface: u32, ts: u64, data: &[u8]) -> Vec<u8> {
+        let mut body = Vec::new();
+        body.extend_from_slice(&iface.to_le_bytes());
+        body.extend_from_slice(&((ts >> 32) as u32).to_le_bytes());
+        body.extend_from_slice(&(ts as u32).to_le_bytes());
+        body.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        body.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        body.extend_from_slice(data);
+        block(PCAPNG_ENHANCED_PACKET, &body)
+    }
+
+    fn pcapng(tsresol: Option<u8>, packets: &[(u64, &[u8])]) -> Vec<u8> {
+        let mut out = section_header();
+        out.extend(interface(LINKTYPE_ETHERNET as u16, tsresol));
+        for (ts, data) in packets {
+            out.extend(enhanced_packet(0, *ts, data));
+        }
+        out
+    }
+
+    /// A classic pcap file, optionally big-endian or with nanosecond
+    /// timestamps
+    fn pcap(big: bool, nanos: bool, link_type: u32, packets: &[(i64, &[u8])]) -> Vec<u8> {
+        let u16 = |v: u16| {
+            if big {
+                v.to_be_bytes()
+            } else {
+                v.to_le_bytes()
+            }
+        };
+        let u32 = |v: u32| {
+            if big {
+                v.to_be_bytes()
+            } else {
+                v.to_le_bytes()
+            }
+        };
+        let magic = if nanos {
+            PCAP_MAGIC_NANOS
+        } else {
+            PCAP_MAGIC_MICROS
+        };
+
+        let mut out = Vec::new();
+        out.extend_from_slice(&u32(magic));
+        out.extend_from_slice(&u16(2));
+        out.extend_from_slice(&u16(4));
+        out.extend_from_slice(&[0; 8]);
+        out.extend_from_slice(&u32(PCAP_SNAPLEN));
+        out.extend_from_slice(&u32(link_type));
+        for (ts_us, data) in packets {
+            let frac = (ts_us % 1_000_000) as u32;
+            out.extend_from_slice(&u32((ts_us / 1_000_000) as u32));
+            out.extend_from_slice(&u32(if nanos { frac * 1000 } else { frac }));
+            out.extend_from_slice(&u32(data.len() as u32));
+            out.extend_from_slice(&u32(data.len() as u32 + 10));
+            out.extend_from_slice(data);
+        }
+        out
+    }
+
+    /// The fields `write` preserves
+    fn summary(capture: &Capture) -> Vec<(u32, i64, u32, Vec<u8>)> {
+        capture
+            .packets
+            .iter()
+            .map(|p| (p.link_type, p.timestamp_us, p.orig_len, p.data.to_vec()))
+            .collect()
+    }
+
+    const PACKETS: &[(i64, &[u8])] = &[
+        (1_700_000_000_000_001, b"first"),
+        (1_700_000_000_500_000, b""),
+        (1_700_000_001_999_999, b"third packet"),
+        (1_700_000_002_000_000, &[0xff; 70]),
+    ];
+
+    #[test]
+    fn pcap_round_trip() {
+        for (big, nanos) in [(false, false), (true, false), (false, true), (true, true)] {
+            let bytes = pcap(big, nanos, LINKTYPE_RAW, PACKETS);
+            let capture = parse(&bytes).unwrap();
+            assert_eq!(capture.packets.len(), PACKETS.len());
+
+            let written = write(&capture.packets).unwrap();
+            let reparsed = parse(&written).unwrap();
+            assert_eq!(
+                summary(&reparsed),
+                summary(&capture),
+                "big {big}, nanos {nanos}"
+            );
+            assert_eq!(reparsed.packets[0].link_type, LINKTYPE_RAW);
+        }
+    }
+
+    #[test]
+    fn pcap_round_trip_subset() {
+        let bytes = pcap(false, false, LINKTYPE_ETHERNET, PACKETS);
+        let capture = parse(&bytes).unwrap();
+
+        let selected: Vec<&Packet> = capture.packets.iter().skip(1).step_by(2).collect();
+        let reparsed_bytes = write(selected.iter().copied()).unwrap();
+        let reparsed = parse(&reparsed_bytes).unwrap();
+
+        assert_eq!(reparsed.packets.len(), 2);
+        assert_eq!(reparsed.packets[0].timestamp_us, PACKETS[1].0);
+        assert_eq!(reparsed.packets[1].data, PACKETS[3].1);
+    }
+
+    #[test]
+    fn pcapng_round_trip() {
+        let packets: Vec<(u64, &[u8])> = PACKETS
+            .iter()
+            .map(|&(ts_us, data)| (ts_us as u64 * 1000, data))
+            .collect();
+        let bytes = pcapng(Some(9), &packets);
+        let capture = parse(&bytes).unwrap();
+
+        let written = write(&capture.packets).unwrap();
+        let reparsed = parse(&written).unwrap();
+        assert_eq!(summary(&reparsed), summary(&capture));
+        assert_eq!(reparsed.packets[2].timestamp_us, PACKETS[2].0);
+    }
+
+    #[test]
+    fn pcapng_round_trip_subset_of_one_interface() {
+        let mut bytes = section_header();
+        bytes.extend(interface(LINKTYPE_ETHERNET as u16, None));
+        bytes.extend(interface(LINKTYPE_RAW as u16, None));
+        bytes.extend(enhanced_packet(0, 1_000_000, b"eth"));
+        bytes.extend(enhanced_packet(1, 2_000_000, b"raw one"));
+        bytes.extend(enhanced_packet(0, 3_000_000, b"eth again"));
+        bytes.extend(enhanced_packet(1, 4_000_000, b"raw two"));
+        let capture = parse(&bytes).unwrap();
+
+        // Mixed link types can't share a classic pcap file
+        assert!(write(&capture.packets).is_err());
+
+        let raw = capture
+            .packets
+            .iter()
+            .filter(|p| p.link_type == LINKTYPE_RAW);
+        let reparsed_bytes = write(raw).unwrap();
+        let reparsed = parse(&reparsed_bytes).unwrap();
+        assert_eq!(
+            summary(&reparsed),
+            vec![
+                (LINKTYPE_RAW, 2_000_000, 7, b"raw one".to_vec()),
+                (LINKTYPE_RAW, 4_000_000, 7, b"raw two".to_vec()),
+            ]
+        );
+    }
+
+    #[test]
+    fn write_empty() {
+        let bytes = write(&[]).unwrap();
+        assert!(parse(&bytes).unwrap().packets.is_empty());
+    }
+
+    #[test]
+    fn pcapng_decimal_tsresol() {
+        let bytes = pcapng(Some(9), &[(1_700_000_000_123_456_789, b"abc")]);
+        let capture = parse(&bytes).unwrap();
+
+        assert_eq!(capture.packets.len(), 1);
+        assert_eq!(capture.packets[0].timestamp_us, 1_700_000_000_123_456);
+        assert_eq!(capture.packets[0].data, b"abc");
+    }
+
+    #[test]
+    fn pcapng_binary_tsresol() {
+        let bytes = pcapng(Some(0x80 | 10), &[(3 * 1024 + 512, b"x")]);
+        let capture = parse(&bytes).unwrap();
+
+        assert_eq!(capture.packets[0].timestamp_us, 3_500_000);
+    }
+
+    #[test]
+    fn pcapng_rejects_huge_tsresol() {
+        for tsresol in [45, 0x7f, 0x80 | 0x7f] {
+            let bytes = pcapng(Some(tsresol), &[(u64::MAX, b"x")]);
+            assert!(parse(&bytes).is_err(), "tsresol {tsresol:#04x}");
+        }
+    }
+
+    #[test]
+    fn pcapng_largest_tsresol() {
+        let bytes = pcapng(Some(38), &[(u64::MAX, b"x")]);
+        let capture = parse(&bytes).unwrap();
+
+        assert_eq!(capture.packets[0].timestamp_us, 0);
+    }
+}
@@ -0,0 +1,797 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::ops::{Range, RangeInclusive};
+
+use serde::{Deserialize, Serialize};
+
+use crate::pcap::{
+    Capture, LINKTYPE_ETHERNET, LINKTYPE_IPV4, LINKTYPE_IPV6, LINKTYPE_LINUX_SLL, LINKTYPE_NULL,
+    LINKTYPE_RAW,
+};
+
+/// Ports AC servers listen on; used to tell which side sent a packet
+pub const AC_SERVER_PORTS: RangeInclusive<u16> = 9000..=9013;
+
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_IPV6: u16 = 0x86dd;
+const ETHERTYPE_VLAN: u16 = 0x8100;
+const IP_PROTOCOL_UDP: u8 = 17;
+
+/// The BSD loopback address family for IPv4, which is the same everywhere
+const AF_INET: u32 = 2;
+/// The same for IPv6, which differs between Linux, NetBSD/OpenBSD, FreeBSD
+/// and macOS
+const AF_INET6: [u32; 4] = [10, 24, 28, 30];
+
+/// The fragment offset bits of the IPv4 flags and fragment offset field
+const IPV4_FRAGMENT_OFFSET: u16 = 0x1fff;
+const IPV6_HEADER_LEN: usize = 40;
+
+/// IPv6 extension headers that can come before the transport header
+const IPV6_HOP_BY_HOP: u8 = 0;
+const IPV6_ROUTING: u8 = 43;
+const IPV6_FRAGMENT: u8 = 44;
+const IPV6_AUTHENTICATION: u8 = 51;
+const IPV6_DESTINATION: u8 = 60;
+
+/// AC packet header flags
+pub mod flags {
+    pub const BLOB_FRAGMENTS: u32 = 0x0000_0004;
+    pub const SERVER_SWITCH: u32 = 0x0000_0100;
+    pub const REQUEST_RETRANSMIT: u32 = 0x0000_1000;
+    pub const REJECT_RETRANSMIT: u32 = 0x0000_2000;
+    pub const ACK_SEQUENCE: u32 = 0x0000_4000;
+    pub const LOGIN_REQUEST: u32 = 0x0001_0000;
+    pub const WORLD_LOGIN_REQUEST: u32 = 0x0002_0000;
+    pub const CONNECT_REQUEST: u32 = 0x0004_0000;
+    pub const CONNECT_RESPONSE: u32 = 0x0008_0000;
+    pub const NET_ERROR: u32 = 0x0010_0000;
+    pub const CICMD_COMMAND: u32 = 0x0040_0000;
+    pub const TIME_SYNC: u32 = 0x0100_0000;
+    pub const ECHO_REQUEST: u32 = 0x0200_0000;
+    pub const ECHO_RESPONSE: u32 = 0x0400_0000;
+    pub const FLOW: u32 = 0x0800_0000;
+}
+
+pub const AC_HEADER_LEN: usize = 20;
+pub const FRAGMENT_HEADER_LEN: usize = 16;
+
+/// Value substituted for the checksum field when hashing a packet header
+const CHECKSUM_SEED: u32 = 0xBADD_70DD;
+
+/// Login authentication types that carry a secret after the account names
+const AUTH_TYPE_PASSWORD: u32 = 0x0000_0002;
+const AUTH_TYPE_GLS_TICKET: u32 = 0x4000_0002;
+
+/// Which side of the connection sent a packet
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    Client,
+    Server,
+    Unknown,
+}
+
+impl Direction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Direction::Client => "client",
+            Direction::Server => "server",
+            Direction::Unknown => "unknown",
+        }
+    }
+
+    fn infer(src_port: u16, dst_port: u16) -> Self {
+        if AC_SERVER_PORTS.contains(&src_port) {
+            Direction::Server
+        } else if AC_SERVER_PORTS.contains(&dst_port) {
+            Direction::Client
+        } else {
+            Direction::Unknown
+        }
+    }
+}
+
+/// The IPv4 or IPv6 header of a link-layer frame, with offsets from the
+/// start of the frame
+#[derive(Debug)]
+pub struct IpPacket {
+    pub version: u8,
+    /// Offset of the IP header
+    pub offset: usize,
+    pub src: IpAddr,
+    pub dst: IpAddr,
+    /// Where the source and destination addresses sit, back to back
+    pub addresses: Range<usize>,
+    /// The transport protocol, past any IPv6 extension headers
+    pub protocol: u8,
+    /// Offset of the transport header, or None in a later fragment
+    pub transport: Option<usize>,
+    /// End of the IP payload, or of the frame if that was cut short
+    pub end: usize,
+}
+
+/// A UDP datagram pulled out of a link-layer frame
+#[derive(Debug)]
+pub struct Datagram<'a> {
+    pub src: IpAddr,
+    pub dst: IpAddr,
+    pub src_port: u16,
+    pub dst_port: u16,
+    /// Where the source and destination addresses sit within the frame
+    pub addresses: Range<usize>,
+    /// Offset of the UDP header within the frame
+    pub udp_offset: usize,
+    pub payload: &'a [u8],
+}
+
+impl Datagram<'_> {
+    pub fn direction(&self) -> Direction {
+        Direction::infer(self.src_port, self.dst_port)
+    }
+}
+
+fn be_u16(bytes: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(
+        bytes.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn le_u16(bytes: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        bytes.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn le_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        bytes.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+/// Find the start of the IP header for a frame of the given link type
+fn ip_offset(link_type: u32, frame: &[u8]) -> Option<usize> {
+    let is_ip = |ethertype| ethertype == ETHERTYPE_IPV4 || ethertype == ETHERTYPE_IPV6;
+
+    match link_type {
+        LINKTYPE_ETHERNET => {
+            let mut offset = 12;
+            let mut ethertype = be_u16(frame, offset)?;
+            while ethertype == ETHERTYPE_VLAN {
+                offset += 4;
+                ethertype = be_u16(frame, offset)?;
+            }
+            is_ip(ethertype).then_some(offset + 2)
+        }
+        LINKTYPE_LINUX_SLL => is_ip(be_u16(frame, 14)?).then_some(16),
+        // The family is in the capturing host's byte order
+        LINKTYPE_NULL => {
+            let family = le_u32(frame, 0)?;
+            [family, family.swap_bytes()]
+                .iter()
+                .any(|family| *family == AF_INET || AF_INET6.contains(family))
+                .then_some(4)
+        }
+        LINKTYPE_RAW | LINKTYPE_IPV4 | LINKTYPE_IPV6 => Some(0),
+        _ => None,
+    }
+}
+
+/// Find the IPv4 or IPv6 header of a frame and the transport header after it
+pub fn ip_packet(link_type: u32, frame: &[u8]) -> Option<IpPacket> {
+    let offset = ip_offset(link_type, frame)?;
+    let ip = frame.get(offset..)?;
+
+    match *ip.first()? >> 4 {
+        4 => {
+            let header = ip.get(..20)?;
+            let ihl = (header[0] & 0x0f) as usize * 4;
+            let total_len = (be_u16(ip, 2)? as usize).min(ip.len());
+            let first_fragment = be_u16(ip, 6)? & IPV4_FRAGMENT_OFFSET == 0;
+
+            Some(IpPacket {
+                version: 4,
+                offset,
+                src: Ipv4Addr::from(<[u8; 4]>::try_from(&header[12..16]).ok()?).into(),
+                dst: Ipv4Addr::from(<[u8; 4]>::try_from(&header[16..20]).ok()?).into(),
+                addresses: offset + 12..offset + 20,
+                protocol: header[9],
+                transport: first_fragment.then_some(offset + ihl),
+                end: offset + total_len,
+            })
+        }
+        6 => {
+            let header = ip.get(..IPV6_HEADER_LEN)?;
+            let total_len = (IPV6_HEADER_LEN + be_u16(ip, 4)? as usize).min(ip.len());
+
+            // Walk the extension headers to the transport header
+            let mut protocol = header[6];
+            let mut transport = IPV6_HEADER_LEN;
+            let mut first_fragment = true;
+            loop {
+                let len = match protocol {
+                    IPV6_HOP_BY_HOP | IPV6_ROUTING | IPV6_DESTINATION => {
+                        (*ip.get(transport + 1)? as usize + 1) * 8
+                    }
+                    IPV6_FRAGMENT => {
+                        first_fragment = be_u16(ip, transport + 2)? >> 3 == 0;
+                        8
+                    }
+                    IPV6_AUTHENTICATION => (*ip.get(transport + 1)? as usize + 2) * 4,
+                    _ => break,
+                };
+                protocol = *ip.get(transport)?;
+                transport += len;
+            }
+
+            Some(IpPacket {
+                version: 6,
+                offset,
+                src: Ipv6Addr::from(<[u8; 16]>::try_from(&header[8..24]).ok()?).into(),
+                dst: Ipv6Addr::from(<[u8; 16]>::try_from(&header[24..40]).ok()?).into(),
+                addresses: offset + 8..offset + 40,
+                protocol,
+                transport: first_fragment.then_some(offset + transport),
+                end: offset + total_len,
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Extract the UDP datagram from an IP frame, if there is one
+pub fn udp_datagram(link_type: u32, frame: &[u8]) -> Option<Datagram<'_>> {
+    let ip = ip_packet(link_type, frame)?;
+    if ip.protocol != IP_PROTOCOL_UDP {
+        return None;
+    }
+    // Later fragments don't start with a UDP header
+    let udp_offset = ip.transport?;
+
+    let udp = frame.get(udp_offset..ip.end)?;
+    let src_port = be_u16(udp, 0)?;
+    let dst_port = be_u16(udp, 2)?;
+    let udp_len = (be_u16(udp, 4)? as usize).clamp(8, udp.len().max(8));
+    let payload = udp.get(8..udp_len)?;
+
+    Some(Datagram {
+        src: ip.src,
+        dst: ip.dst,
+        src_port,
+        dst_port,
+        addresses: ip.addresses,
+        udp_offset,
+        payload,
+    })
+}
+
+/// A fragment of a game message carried inside an AC packet
+#[derive(Debug)]
+pub struct Fragment<'a> {
+    pub count: u16,
+    pub index: u16,
+    pub header: &'a [u8],
+    pub data: &'a [u8],
+}
+
+impl Fragment<'_> {
+    /// The message opcode, only present in the first fragment of a message
+    pub fn opcode(&self) -> Option<u32> {
+        if self.index == 0 {
+            le_u32(self.data, 0)
+        } else {
+            None
+        }
+    }
+}
+
+/// A decoded AC transport packet
+#[derive(Debug)]
+pub struct AcPacket<'a> {
+    pub sequence: u32,
+    pub flags: u32,
+    pub checksum: u32,
+    pub header: &'a [u8],
+    /// Optional header data between the packet header and the fragments
+    pub optional: &'a [u8],
+    pub fragments: Vec<Fragment<'a>>,
+}
+
+impl AcPacket<'_> {
+    /// Compute the unencrypted packet checksum
+    ///
+    /// Packets flagged with an encrypted checksum XOR this with a key from the
+    /// session's ISAAC stream, so it only matches the stored value for
+    /// unencrypted packets like login requests.
+    pub fn compute_checksum(&self) -> u32 {
+        let mut header = self.header.to_vec();
+        header[8..12].copy_from_slice(&CHECKSUM_SEED.to_le_bytes());
+
+        self.fragments.iter().fold(
+            hash32(&header).wrapping_add(hash32(self.optional)),
+            |sum, f| {
+                sum.wrapping_add(hash32(f.header))
+                    .wrapping_add(hash32(f.data))
+            },
+        )
+    }
+}
+
+/// AC's additive checksum over a byte slice
+fn hash32(data: &[u8]) -> u32 {
+    let mut checksum = (data.len() as u32) << 16;
+
+    let mut chunks = data.chunks_exact(4);
+    for chunk in &mut chunks {
+        checksum = checksum.wrapping_add(u32::from_le_bytes(chunk.try_into().unwrap()));
+    }
+
+    for (i, &byte) in chunks.remainder().iter().enumerate() {
+        checksum = checksum.wrapping_add((byte as u32) << (8 * (3 - i)));
+    }
+
+    checksum
+}
+
+/// Length of the optional headers present for `packet_flags`, given the
+/// bytes following the packet header
+fn optional_len(packet_flags: u32, body: &[u8]) -> Option<usize> {
+    // Login requests consume the rest of the packet
+    if packet_flags & flags::LOGIN_REQUEST != 0 {
+        return Some(body.len());
+    }
+
+    let mut len = 0;
+    let fixed = [
+        (flags::SERVER_SWITCH, 8),
+        (flags::REQUEST_RETRANSMIT, 0),
+        (flags::REJECT_RETRANSMIT, 0),
+        (flags::ACK_SEQUENCE, 4),
+        (flags::WORLD_LOGIN_REQUEST, 8),
+        (flags::CONNECT_REQUEST, 32),
+        (flags::CONNECT_RESPONSE, 8),
+        (flags::NET_ERROR, 8),
+        (flags::CICMD_COMMAND, 8),
+        (flags::TIME_SYNC, 8),
+        (flags::ECHO_REQUEST, 4),
+        (flags::ECHO_RESPONSE, 8),
+        (flags::FLOW, 6),
+    ];
+
+    for (flag, size) in fixed {
+        if packet_flags & flag == 0 {
+            continue;
+        }
+        len += if flag == flags::REQUEST_RETRANSMIT || flag == flags::REJECT_RETRANSMIT {
+            4 + le_u32(body, len)? as usize * 4
+        } else {
+            size
+        };
+    }
+
+    (len <= body.len()).then_some(len)
+}
+
+/// Decode an AC packet from a UDP payload
+pub fn parse_ac_packet(payload: &[u8]) -> Option<AcPacket<'_>> {
+    let sequence = le_u32(payload, 0)?;
+    let packet_flags = le_u32(payload, 4)?;
+    let checksum = le_u32(payload, 8)?;
+    let size = le_u16(payload, 16)?;
+
+    let end = (AC_HEADER_LEN + size as usize).min(payload.len());
+    let body = payload.get(AC_HEADER_LEN..end)?;
+    let optional_len = optional_len(packet_flags, body)?;
+
+    let mut fragments = Vec::new();
+    if packet_flags & flags::BLOB_FRAGMENTS != 0 {
+        let mut offset = optional_len;
+        while offset + FRAGMENT_HEADER_LEN <= body.len() {
+            let frag_size = le_u16(body, offset + 10)? as usize;
+            if frag_size < FRAGMENT_HEADER_LEN || offset + frag_size > body.len() {
+                break;
+            }
+
+            fragments.push(Fragment {
+                count: le_u16(body, offset + 8)?,
+                index: le_u16(body, offset + 12)?,
+                header: &body[offset..offset + FRAGMENT_HEADER_LEN],
+                data: &body[offset + FRAGMENT_HEADER_LEN..offset + frag_size],
+            });
+
+            offset += frag_size;
+        }
+    }
+
+    Some(AcPacket {
+        sequence,
+        flags: packet_flags,
+        checksum,
+        header: &payload[..AC_HEADER_LEN],
+        optional: &body[..optional_len],
+        fragments,
+    })
+}
+
+/// The interesting parts of a client login request
+#[derive(Debug)]
+pub struct LoginRequest {
+    pub account: String,
+    /// Whether a password or GLS ticket follows the account names
+    pub has_secret: bool,
+    /// Byte ranges within the login data holding account names and secrets
+    pub sensitive: Vec<Range<usize>>,
+}
+
+/// Read a u16-length-prefixed string padded to a 4-byte boundary, returning
+/// the range of its characters and the offset just past it
+///
+/// The range stops at the end of the data if the string was cut off.
+fn string16l(data: &[u8], offset: usize) -> Option<(Range<usize>, usize)> {
+    let len = le_u16(data, offset)? as usize;
+    let start = offset + 2;
+    Some((
+        start..(start + len).min(data.len()),
+        (start + len).next_multiple_of(4),
+    ))
+}
+
+/// Parse the optional data of a packet flagged as a login request
+pub fn parse_login_request(data: &[u8]) -> Option<LoginRequest> {
+    // Client version, then the remaining length, auth type, flags and timestamp
+    let (_, offset) = string16l(data, 0)?;
+    let auth_type = le_u32(data, offset + 4)?;
+
+    let (account, offset) = string16l(data, offset + 16)?;
+    let mut sensitive = vec![account.clone()];
+
+    // Everything after the account name may be cut off
+    let mut has_secret = false;
+    if let Some((login_as, offset)) = string16l(data, offset) {
+        sensitive.push(login_as);
+
+        if auth_type == AUTH_TYPE_PASSWORD || auth_type == AUTH_TYPE_GLS_TICKET {
+            let len = le_u32(data, offset).unwrap_or_default() as usize;
+            // Either end may be past the data if the packet was truncated
+            let start = (offset + 4).min(data.len());
+            let secret = start..(start + len).min(data.len());
+            has_secret = !secret.is_empty() && data[secret.clone()].iter().any(|&b| b != 0);
+            sensitive.push(secret);
+        }
+    }
+
+    Some(LoginRequest {
+        account: String::from_utf8_lossy(&data[account]).into_owned(),
+        has_secret,
+        sensitive,
+    })
+}
+
+/// Find the first login request in a capture that still carries an account
+/// name or secret
+pub fn find_credentials(capture: &Capture) -> Option<LoginRequest> {
+    capture.packets.iter().find_map(|packet| {
+        let datagram = udp_datagram(packet.link_type, packet.data)?;
+        let ac = parse_ac_packet(datagram.payload)?;
+        if ac.flags & flags::LOGIN_REQUEST == 0 {
+            return None;
+        }
+        parse_login_request(ac.optional)
+            .filter(|login| login.has_secret || login.account.bytes().any(|b| b != b'*'))
+    })
+}
+
+/// Human-readable name for a game message opcode
+pub fn message_name(opcode: u32) -> Option<&'static str> {
+    let name = match opcode {
+        0x0024 => "InventoryRemoveObject",
+        0x0197 => "SetStackSize",
+        0x01E0 => "EmoteText",
+        0x02BB => "HearSpeech",
+        0x02BC => "HearRangedSpeech",
+        0x02CD => "PrivateUpdatePropertyInt",
+        0x02E7 => "PrivateUpdateVital",
+        0xF625 => "ObjDescEvent",
+        0xF653 => "CharacterLogOff",
+        0xF657 => "CharacterEnterWorld",
+        0xF658 => "CharacterList",
+        0xF745 => "ObjectCreate",
+        0xF746 => "PlayerCreate",
+        0xF747 => "ObjectDelete",
+        0xF748 => "UpdatePosition",
+        0xF74B => "SetState",
+        0xF74C => "UpdateMotion",
+        0xF74E => "VectorUpdate",
+        0xF750 => "Sound",
+        0xF753 => "AutonomousPosition",
+        0xF755 => "PlayEffect",
+        0xF7B0 => "GameEvent",
+        0xF7B1 => "GameAction",
+        0xF7C8 => "CharacterEnterWorldRequest",
+        0xF7DE => "TurbineChat",
+        0xF7DF => "CharacterEnterWorldServerReady",
+        0xF7E0 => "ServerMessage",
+        0xF7E1 => "ServerName",
+        0xF7E5 => "DddInterrogation",
+        0xF7E6 => "DddInterrogationResponse",
+        _ => return None,
+    };
+
+    Some(name)
+}
+
+/// Parse an opcode written as hex (`0xF7B1`) or decimal
+pub fn parse_opcode(value: &str) -> Option<u32> {
+    let value = value.trim();
+    match value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => value.parse().ok(),
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DecodedMessage {
+    pub opcode: u32,
+    pub name: Option<&'static str>,
+    pub fragment_count: u16,
+    pub size: usize,
+}
+
+/// A capture packet with its network and AC layers decoded
+#[derive(Debug, Clone, Serialize)]
+pub struct DecodedPacket {
+    pub index: usize,
+    /// Microseconds since the Unix epoch
+    pub timestamp_us: i64,
+    pub direction: Direction,
+    pub src: String,
+    pub dst: String,
+    /// Length of the frame on the wire
+    pub length: u32,
+    /// Length of the UDP payload
+    pub payload_size: usize,
+    pub sequence: Option<u32>,
+    pub flags: Option<u32>,
+    pub messages: Vec<DecodedMessage>,
+}
+
+impl DecodedPacket {
+    pub fn has_opcode(&self, opcode: u32) -> bool {
+        self.messages.iter().any(|m| m.opcode == opcode)
+    }
+}
+
+/// Criteria for selecting decoded packets; unset fields match everything
+#[derive(Debug, Default)]
+pub struct PacketFilter {
+    pub opcode: Option<u32>,
+    pub direction: Option<Direction>,
+    /// Inclusive time window in microseconds since the Unix epoch
+    pub from_us: Option<i64>,
+    pub to_us: Option<i64>,
+}
+
+impl PacketFilter {
+    pub fn matches(&self, packet: &DecodedPacket) -> bool {
+        self.opcode.is_none_or(|op| packet.has_opcode(op))
+            && self.direction.is_none_or(|d| packet.direction == d)
+            && self.from_us.is_none_or(|t| packet.timestamp_us >= t)
+            && self.to_us.is_none_or(|t| packet.timestamp_us <= t)
+    }
+}
+
+/// Decode every UDP packet in a capture
+pub fn decode(capture: &Capture) -> Vec<DecodedPacket> {
+    capture
+        .packets
+        .iter()
+        .enumerate()
+        .filter_map(|(index, packet)| {
+            let datagram = udp_datagram(packet.link_type, packet.data)?;
+            let ac = parse_ac_packet(datagram.payload);
+
+            let messages = ac
+                .as_ref()
+                .map(|ac| {
+                    ac.fragments
+                        .iter()
+                        .filter_map(|f| {
+                            f.opcode().map(|opcode| DecodedMessage {
+                                opcode,
+                                name: message_name(opcode),
+                                fragment_count: f.count,
+                                size: f.data.len(),
+                            })
+                        })
+                        .collect()
+                })
+                .unwrap_or_default();
+
+            Some(DecodedPacket {
+                index,
+                timestamp_us: packet.timestamp_us,
+                direction: datagram.direction(),
+                src: SocketAddr::new(datagram.src, datagram.src_port).to_string(),
+                dst: SocketAddr::new(datagram.dst, datagram.dst_port).to_string(),
+                length: packet.orig_len,
+                payload_size: datagram.payload.len(),
+                sequence: ac.as_ref().map(|ac| ac.sequence),
+                flags: ac.as_ref().map(|ac| ac.flags),
+                messages,
+            })
+        })
+        .collect()
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Render decoded packets as CSV, one row per game message
+///
+/// Packets without any game messages (acks, pings, ...) still get a row with
+/// the message columns left empty.
+pub fn to_csv<'a>(packets: impl IntoIterator<Item = &'a DecodedPacket>) -> String {
+    let mut out = String::from(
+        "index,timestamp_us,direction,src,dst,length,payload_size,sequence,flags,opcode,message,fragment_count,message_size\n",
+    );
+
+    for packet in packets {
+        let prefix = [
+            packet.index.to_string(),
+            packet.timestamp_us.to_string(),
+            packet.direction.as_str().to_string(),
+            csv_field(&packet.src),
+            csv_field(&packet.dst),
+            packet.length.to_string(),
+            packet.payload_size.to_string(),
+            packet.sequence.map(|s| s.to_string()).unwrap_or_default(),
+            packet
+                .flags
+                .map(|f| format!("{f:#010x}"))
+                .unwrap_or_default(),
+        ]
+        .join(",");
+
+        if packet.messages.is_empty() {
+            out.push_str(&prefix);
+            out.push_str(",,,,\n");
+            continue;
+        }
+
+        for message in &packet.messages {
+            out.push_str(&prefix);
+            out.push_str(&format!(
+                ",{:#06x},{},{},{}\n",
+                message.opcode,
+                csv_field(message.name.unwrap_or_default()),
+                message.fragment_count,
+                message.size
+            ));
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string16l(out: &mut Vec<u8>, value: &str) {
+        out.extend_from_slice(&(value.len() as u16).to_le_bytes());
+        out.extend_from_slice(value.as_bytes());
+        out.resize(out.len().next_multiple_of(4), 0);
+    }
+
+    /// Login request data up to and including the account names
+    fn login_names(auth_type: u32, account: &str, login_as: &str) -> Vec<u8> {
+        let mut out = Vec::new();
+        string16l(&mut out, "1802");
+        out.extend_from_slice(&0u32.to_le_bytes());
+        out.extend_from_slice(&auth_type.to_le_bytes());
+        out.extend_from_slice(&[0; 8]);
+        string16l(&mut out, account);
+        string16l(&mut out, login_as);
+        out
+    }
+
+    #[test]
+    fn login_request_with_password() {
+        let mut data = login_names(AUTH_TYPE_PASSWORD, "account", "");
+        data.extend_from_slice(&6u32.to_le_bytes());
+        data.extend_from_slice(b"hunter");
+
+        let login = parse_login_request(&data).unwrap();
+        assert_eq!(login.account, "account");
+        assert!(login.has_secret);
+        assert_eq!(&data[login.sensitive[2].clone()], b"hunter");
+    }
+
+    #[test]
+    fn login_request_truncated_secret() {
+        let mut data = login_names(AUTH_TYPE_PASSWORD, "account", "");
+        data.extend_from_slice(&100u32.to_le_bytes());
+        data.extend_from_slice(b"hun");
+
+        let login = parse_login_request(&data).unwrap();
+        assert_eq!(&data[login.sensitive[2].clone()], b"hun");
+    }
+
+    #[test]
+    fn login_request_truncated_account() {
+        let mut data = login_names(AUTH_TYPE_PASSWORD, "account", "other");
+        // Drop the second name, the padding and "ount"
+        data.truncate(data.len() - 15);
+
+        let login = parse_login_request(&data).unwrap();
+        assert_eq!(login.account, "acc");
+        assert_eq!(login.sensitive, vec![data.len() - 3..data.len()]);
+    }
+
+    #[test]
+    fn login_request_truncated_before_secret() {
+        let full = login_names(AUTH_TYPE_GLS_TICKET, "account", "");
+        // Cut inside the padding after the last name, then before the length
+        for cut in [full.len() - 2, full.len(), full.len() + 2] {
+            let mut data = full.clone();
+            data.resize(cut, 0);
+
+            let login = parse_login_request(&data).unwrap();
+            assert!(!login.has_secret);
+            for range in &login.sensitive {
+                assert!(range.start <= range.end && range.end <= data.len());
+            }
+        }
+    }
+
+    /// An IPv6 header with the given payload length and next header
+    fn ipv6_header(payload_len: usize, next: u8) -> Vec<u8> {
+        let mut header = vec![0x60, 0, 0, 0];
+        header.extend_from_slice(&(payload_len as u16).to_be_bytes());
+        header.extend_from_slice(&[next, 64]);
+        header.extend_from_slice(&"2001:db8::1".parse::<Ipv6Addr>().unwrap().octets());
+        header.extend_from_slice(&"2001:db8::2".parse::<Ipv6Addr>().unwrap().octets());
+        header
+    }
+
+    #[test]
+    fn ipv6_over_loopback() {
+        let udp = [0xc3, 0x50, 0x23, 0x28, 0, 12, 0, 0, 1, 2, 3, 4];
+        // AF_INET6 as macOS writes it, in either byte order
+        for family in [30u32.to_le_bytes(), 30u32.to_be_bytes()] {
+            let mut frame = family.to_vec();
+            frame.extend_from_slice(&ipv6_header(udp.len(), IP_PROTOCOL_UDP));
+            frame.extend_from_slice(&udp);
+
+            let datagram = udp_datagram(LINKTYPE_NULL, &frame).unwrap();
+            assert_eq!(datagram.src, "2001:db8::1".parse::<IpAddr>().unwrap());
+            assert_eq!(datagram.direction(), Direction::Client);
+            assert_eq!(datagram.addresses, 12..44);
+            assert_eq!(datagram.payload, &[1, 2, 3, 4]);
+        }
+    }
+
+    #[test]
+    fn ipv6_fragments() {
+        // A fragment header at offset 0, then one at offset 8
+        for (offset, first) in [(0u16, true), (1, false)] {
+            let mut frame = ipv6_header(8 + 12, IPV6_FRAGMENT);
+            frame.extend_from_slice(&[IP_PROTOCOL_UDP, 0]);
+            frame.extend_from_slice(&(offset << 3).to_be_bytes());
+            frame.extend_from_slice(&[0; 4]);
+            frame.extend_from_slice(&[0xc3, 0x50, 0x23, 0x28, 0, 12, 0, 0, 1, 2, 3, 4]);
+
+            let ip = ip_packet(LINKTYPE_RAW, &frame).unwrap();
+            assert_eq!(ip.protocol, IP_PROTOCOL_UDP);
+            assert_eq!(ip.transport, first.then_some(48));
+            assert_eq!(udp_datagram(LINKTYPE_RAW, &frame).is_some(), first);
+        }
+    }
+}
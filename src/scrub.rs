@@ -0,0 +1,429 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use anyhow::Result;
+
+use crate::pcap;
+use crate::protocol::{self, AC_HEADER_LEN, flags};
+
+const IP_PROTOCOL_TCP: u8 = 6;
+const IP_PROTOCOL_UDP: u8 = 17;
+const IP_PROTOCOL_ICMPV6: u8 = 58;
+
+/// What was changed while scrubbing a capture
+#[derive(Debug, Default)]
+pub struct ScrubReport {
+    pub packets: usize,
+    pub addresses: usize,
+    pub logins: usize,
+}
+
+/// Maps real addresses onto 10.0.0.0/8 and fd00::/8 in order of first
+/// appearance, so the same host keeps the same address throughout a capture
+#[derive(Default)]
+struct AddressMap {
+    v4: HashMap<Ipv4Addr, Ipv4Addr>,
+    v6: HashMap<Ipv6Addr, Ipv6Addr>,
+}
+
+impl AddressMap {
+    fn map(&mut self, addr: IpAddr) -> IpAddr {
+        match addr {
+            IpAddr::V4(addr) => {
+                let next = self.v4.len() as u32 + 1;
+                IpAddr::V4(
+                    *self
+                        .v4
+                        .entry(addr)
+                        .or_insert_with(|| Ipv4Addr::from(0x0a00_0000 | (next & 0x00ff_ffff))),
+                )
+            }
+            IpAddr::V6(addr) => {
+                let next = self.v6.len() as u128 + 1;
+                IpAddr::V6(
+                    *self
+                        .v6
+                        .entry(addr)
+                        .or_insert_with(|| Ipv6Addr::from((0xfd << 120) | next)),
+                )
+            }
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.v4.len() + self.v6.len()
+    }
+}
+
+fn octets(addr: IpAddr) -> Vec<u8> {
+    match addr {
+        IpAddr::V4(addr) => addr.octets().to_vec(),
+        IpAddr::V6(addr) => addr.octets().to_vec(),
+    }
+}
+
+fn read_be_u16(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_be_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+/// Fold a one's complement sum down to 16 bits
+fn fold(mut sum: u32) -> u16 {
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    sum as u16
+}
+
+/// Incrementally update an internet checksum after replacing `old` with `new`
+/// (RFC 1624), which works even when the covered data was truncated
+fn adjust_checksum(checksum: u16, old: &[u8], new: &[u8]) -> u16 {
+    let mut sum = (!checksum) as u32;
+    for (o, n) in old.chunks(2).zip(new.chunks(2)) {
+        sum += (!u16::from_be_bytes([o[0], o[1]])) as u32;
+        sum += u16::from_be_bytes([n[0], n[1]]) as u32;
+    }
+    !fold(sum)
+}
+
+/// Compute a UDP checksum from scratch over the pseudo-header and datagram
+///
+/// The IPv4 and IPv6 pseudo-headers add up the same way for datagrams
+/// shorter than 64 KiB, so this works for both.
+fn udp_checksum(src: &[u8], dst: &[u8], udp: &[u8]) -> u16 {
+    let mut sum: u32 = 0;
+    for pair in src.chunks(2).chain(dst.chunks(2)) {
+        sum += u16::from_be_bytes([pair[0], pair[1]]) as u32;
+    }
+    sum += IP_PROTOCOL_UDP as u32 + udp.len() as u32;
+
+    for (i, pair) in udp.chunks(2).enumerate() {
+        // Skip the checksum field itself
+        if i == 3 {
+            continue;
+        }
+        let hi = pair[0];
+        let lo = pair.get(1).copied().unwrap_or(0);
+        sum += u16::from_be_bytes([hi, lo]) as u32;
+    }
+
+    match !fold(sum) {
+        0 => 0xffff,
+        checksum => checksum,
+    }
+}
+
+/// Rewrite the addresses of one IP frame, fixing the IPv4 header and
+/// transport checksums. Returns false when the frame isn't IP.
+fn scrub_addresses(link_type: u32, frame: &mut [u8], map: &mut AddressMap) -> bool {
+    let Some(ip) = protocol::ip_packet(link_type, frame) else {
+        return false;
+    };
+
+    let old = frame[ip.addresses.clone()].to_vec();
+    let mut new = octets(map.map(ip.src));
+    new.extend(octets(map.map(ip.dst)));
+    frame[ip.addresses.clone()].copy_from_slice(&new);
+
+    // IPv6 has no header checksum
+    if ip.version == 4 {
+        let at = ip.offset + 10;
+        let ip_checksum = adjust_checksum(read_be_u16(frame, at), &old, &new);
+        frame[at..at + 2].copy_from_slice(&ip_checksum.to_be_bytes());
+    }
+
+    // Only the first fragment starts with the transport header
+    let Some(transport) = ip.transport else {
+        return true;
+    };
+
+    // TCP, UDP and ICMPv6 checksums cover the addresses through the
+    // pseudo-header
+    let checksum_at = match ip.protocol {
+        IP_PROTOCOL_UDP => transport + 6,
+        IP_PROTOCOL_TCP => transport + 16,
+        IP_PROTOCOL_ICMPV6 if ip.version == 6 => transport + 2,
+        _ => return true,
+    };
+    if frame.len() < checksum_at + 2 {
+        return true;
+    }
+
+    let checksum = read_be_u16(frame, checksum_at);
+    // A zero UDP checksum means none was computed
+    if ip.protocol == IP_PROTOCOL_UDP && checksum == 0 {
+        return true;
+    }
+    let checksum = match adjust_checksum(checksum, &old, &new) {
+        0 if ip.protocol == IP_PROTOCOL_UDP => 0xffff,
+        checksum => checksum,
+    };
+    frame[checksum_at..checksum_at + 2].copy_from_slice(&checksum.to_be_bytes());
+
+    true
+}
+
+/// Blank account names and secrets in an AC login request, fixing the AC and
+/// UDP checksums. Returns whether anything was blanked.
+fn scrub_login(link_type: u32, frame: &mut [u8]) -> bool {
+    let Some(datagram) = protocol::udp_datagram(link_type, frame) else {
+        return false;
+    };
+    let Some(packet) = protocol::parse_ac_packet(datagram.payload) else {
+        return false;
+    };
+    if packet.flags & flags::LOGIN_REQUEST == 0 {
+        return false;
+    }
+    let Some(login) = protocol::parse_login_request(packet.optional) else {
+        return false;
+    };
+
+    // Only rewrite the AC checksum if we could reproduce it to begin with
+    let checksum_valid = packet.compute_checksum() == packet.checksum;
+    let udp = datagram.udp_offset;
+    let addresses = datagram.addresses.clone();
+    let payload = udp + 8;
+    let optional = payload + AC_HEADER_LEN;
+
+    for range in &login.sensitive {
+        frame[optional + range.start..optional + range.end].fill(b'*');
+    }
+
+    if checksum_valid && let Some(packet) = protocol::parse_ac_packet(&frame[payload..]) {
+        let checksum = packet.compute_checksum();
+        frame[payload + 8..payload + 12].copy_from_slice(&checksum.to_le_bytes());
+    }
+
+    let udp_len = read_be_u16(frame, udp + 4) as usize;
+    if read_be_u16(frame, udp + 6) != 0 {
+        let checksum = match frame.get(udp..udp + udp_len) {
+            Some(datagram) => {
+                let (src, dst) = frame[addresses.clone()].split_at(addresses.len() / 2);
+                udp_checksum(src, dst, datagram)
+            }
+            // Truncated: we can't recompute it, so drop it
+            None => 0,
+        };
+        frame[udp + 6..udp + 8].copy_from_slice(&checksum.to_be_bytes());
+    }
+
+    true
+}
+
+/// Anonymize a capture: remap IPv4 and IPv6 addresses consistently and blank AC login
+/// account names and credentials
+///
+/// The capture is rewritten in place, so the output keeps the input's format
+/// (pcap or pcapng) and framing.
+pub fn scrub(bytes: &[u8]) -> Result<(Vec<u8>, ScrubReport)> {
+    let capture = pcap::parse(bytes)?;
+    let frames: Vec<(u32, usize, usize)> = capture
+        .packets
+        .iter()
+        .map(|p| (p.link_type, p.offset, p.data.len()))
+        .collect();
+
+    let mut out = bytes.to_vec();
+    let mut map = AddressMap::default();
+    let mut report = ScrubReport::default();
+
+    for (link_type, offset, len) in frames {
+        let frame = &mut out[offset..offset + len];
+        if scrub_addresses(link_type, frame, &mut map) {
+            report.packets += 1;
+        }
+        if scrub_login(link_type, frame) {
+            report.logins += 1;
+        }
+    }
+
+    report.addresses = map.len();
+
+    Ok((out, report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ops::Range;
+
+    use crate::pcap::{LINKTYPE_ETHERNET, LINKTYPE_RAW, Packet};
+
+    /// A UDP datagram carrying an AC login request with `login` as its
+    /// optional data, without a checksum
+    fn login_datagram(login: &[u8]) -> Vec<u8> {
+        let udp_len = 8 + AC_HEADER_LEN + login.len();
+
+        let mut udp = Vec::new();
+        udp.extend_from_slice(&50000u16.to_be_bytes());
+        udp.extend_from_slice(&9000u16.to_be_bytes());
+        udp.extend_from_slice(&(udp_len as u16).to_be_bytes());
+        udp.extend_from_slice(&0u16.to_be_bytes());
+
+        let mut header = [0u8; AC_HEADER_LEN];
+        header[4..8].copy_from_slice(&flags::LOGIN_REQUEST.to_le_bytes());
+        header[16..18].copy_from_slice(&(login.len() as u16).to_le_bytes());
+        udp.extend_from_slice(&header);
+        udp.extend_from_slice(login);
+
+        udp
+    }
+
+    /// A raw IPv4 frame carrying [`login_datagram`]
+    fn login_frame(login: &[u8]) -> Vec<u8> {
+        let udp = login_datagram(login);
+        let total_len = 20 + udp.len();
+
+        let mut frame = vec![0x45, 0, 0, 0, 0, 0, 0, 0, 64, IP_PROTOCOL_UDP, 0, 0];
+        frame[2..4].copy_from_slice(&(total_len as u16).to_be_bytes());
+        frame.extend_from_slice(&[192, 168, 1, 2, 203, 0, 113, 7]);
+        frame.extend_from_slice(&udp);
+
+        frame
+    }
+
+    /// Where the addresses sit in [`ipv6_login_frame`]
+    const IPV6_ADDRESSES: Range<usize> = 22..54;
+    /// Where the UDP header starts in [`ipv6_login_frame`]
+    const IPV6_UDP: usize = 62;
+
+    /// An Ethernet frame carrying [`login_datagram`] over IPv6, behind a
+    /// hop-by-hop options header, with a valid UDP checksum
+    fn ipv6_login_frame(login: &[u8]) -> Vec<u8> {
+        let mut udp = login_datagram(login);
+        let src: Ipv6Addr = "2001:db8::1".parse().unwrap();
+        let dst: Ipv6Addr = "2001:db8::2".parse().unwrap();
+        let checksum = udp_checksum(&src.octets(), &dst.octets(), &udp);
+        udp[6..8].copy_from_slice(&checksum.to_be_bytes());
+
+        let mut frame = vec![0; 12];
+        frame.extend_from_slice(&0x86ddu16.to_be_bytes());
+        frame.extend_from_slice(&[0x60, 0, 0, 0]);
+        frame.extend_from_slice(&((8 + udp.len()) as u16).to_be_bytes());
+        // Hop-by-hop options next, then a hop limit
+        frame.extend_from_slice(&[0, 64]);
+        frame.extend_from_slice(&src.octets());
+        frame.extend_from_slice(&dst.octets());
+        // UDP next, 8 bytes long, holding a PadN option
+        frame.extend_from_slice(&[IP_PROTOCOL_UDP, 0, 1, 4, 0, 0, 0, 0]);
+        frame.extend_from_slice(&udp);
+
+        frame
+    }
+
+    /// Where the account name's characters start in [`login_data`]
+    const ACCOUNT_OFFSET: usize = 26;
+
+    /// Login request data for a password login, cut off after `len` bytes
+    fn login_data(len: usize) -> Vec<u8> {
+        let mut data = vec![4, 0];
+        data.extend_from_slice(b"1802");
+        data.extend_from_slice(&[0; 2]);
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data.extend_from_slice(&2u32.to_le_bytes());
+        data.extend_from_slice(&[0; 8]);
+        data.extend_from_slice(&[7, 0]);
+        data.extend_from_slice(b"account");
+        data.extend_from_slice(&[0; 3]);
+        data.extend_from_slice(&[0, 0]);
+        data.extend_from_slice(&[0; 2]);
+        data.extend_from_slice(&6u32.to_le_bytes());
+        data.extend_from_slice(b"hunter");
+        data.truncate(len);
+        data
+    }
+
+    fn scrub_frame(frame: &[u8]) -> (Vec<u8>, ScrubReport) {
+        scrub_frame_as(LINKTYPE_RAW, frame)
+    }
+
+    fn scrub_frame_as(link_type: u32, frame: &[u8]) -> (Vec<u8>, ScrubReport) {
+        let packet = Packet {
+            link_type,
+            timestamp_us: 0,
+            orig_len: frame.len() as u32,
+            offset: 0,
+            data: frame,
+        };
+        let (out, report) = scrub(&pcap::write([&packet]).unwrap()).unwrap();
+        let capture = pcap::parse(&out).unwrap();
+        (capture.packets[0].data.to_vec(), report)
+    }
+
+    #[test]
+    fn scrubs_login() {
+        let frame = login_frame(&login_data(usize::MAX));
+        let (out, report) = scrub_frame(&frame);
+
+        assert_eq!(report.logins, 1);
+        assert_eq!(report.addresses, 2);
+        assert!(!out.windows(7).any(|w| w == b"account"));
+        assert!(!out.windows(6).any(|w| w == b"hunter"));
+        assert_eq!(&out[12..16], &[10, 0, 0, 1]);
+    }
+
+    #[test]
+    fn scrubs_ipv6() {
+        let frame = ipv6_login_frame(&login_data(usize::MAX));
+        let (out, report) = scrub_frame_as(LINKTYPE_ETHERNET, &frame);
+
+        assert_eq!(report.packets, 1);
+        assert_eq!(report.addresses, 2);
+        assert_eq!(report.logins, 1);
+        assert!(!out.windows(7).any(|w| w == b"account"));
+
+        let (src, dst) = out[IPV6_ADDRESSES].split_at(16);
+        assert_eq!(src, "fd00::1".parse::<Ipv6Addr>().unwrap().octets());
+        assert_eq!(dst, "fd00::2".parse::<Ipv6Addr>().unwrap().octets());
+
+        let udp = &out[IPV6_UDP..];
+        assert_eq!(read_be_u16(udp, 6), udp_checksum(src, dst, udp));
+    }
+
+    #[test]
+    fn fixes_ipv6_checksums_without_a_login() {
+        let mut frame = ipv6_login_frame(&login_data(usize::MAX));
+        // Not a login request any more, so only the addresses change
+        frame[IPV6_UDP + 8 + 6] = 0;
+        let (src, dst) = frame[IPV6_ADDRESSES].split_at(16);
+        let checksum = udp_checksum(src, dst, &frame[IPV6_UDP..]);
+        frame[IPV6_UDP + 6..IPV6_UDP + 8].copy_from_slice(&checksum.to_be_bytes());
+
+        let (out, report) = scrub_frame_as(LINKTYPE_ETHERNET, &frame);
+        assert_eq!(report.logins, 0);
+
+        let (src, dst) = out[IPV6_ADDRESSES].split_at(16);
+        let udp = &out[IPV6_UDP..];
+        assert_eq!(read_be_u16(udp, 6), udp_checksum(src, dst, udp));
+    }
+
+    #[test]
+    fn leaves_later_fragments_payload_alone() {
+        let mut frame = login_frame(&login_data(usize::MAX));
+        // A fragment at offset 8, whose "UDP checksum" is really payload
+        frame[6..8].copy_from_slice(&1u16.to_be_bytes());
+        frame[26..28].copy_from_slice(&[0x12, 0x34]);
+        let (out, report) = scrub_frame(&frame);
+
+        assert_eq!(report.packets, 1);
+        assert_eq!(report.logins, 0);
+        assert_eq!(&out[20..], &frame[20..]);
+    }
+
+    #[test]
+    fn scrubs_truncated_login() {
+        let full = login_data(usize::MAX).len();
+        // Where the account name sits within the frame
+        let account = 20 + 8 + AC_HEADER_LEN + ACCOUNT_OFFSET;
+        // Every cut from the first byte of the account name to the full
+        // packet
+        for len in ACCOUNT_OFFSET..=full {
+            let frame = login_frame(&login_data(len));
+            let (out, report) = scrub_frame(&frame);
+
+            assert_eq!(report.logins, 1, "cut at {len}");
+            let name = &out[account..(account + 7).min(out.len())];
+            assert!(name.iter().all(|&b| b == b'*'), "cut at {len}");
+        }
+    }
+}
@@ -0,0 +1,134 @@
+use std::sync::atomic::{AtomicI64, Ordering};
+
+use serde::Deserialize;
+
+use crate::db::Alias;
+use crate::metrics;
+
+/// Unix timestamp of the last successful servers.json fetch, 0 if none yet
+static LAST_FETCH: AtomicI64 = AtomicI64::new(0);
+
+#[derive(Debug, Deserialize)]
+#[allow(dead_code)]
+pub struct PlayerInfo {
+    pub count: u32,
+    pub updated_at: String,
+    pub age: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[allow(dead_code)]
+pub struct ServerInfo {
+    pub name: String,
+    pub description: String,
+    #[serde(rename = "type")]
+    pub server_type: String,
+    pub software: String,
+    pub host: String,
+    pub port: String,
+    pub website_url: Option<String>,
+    pub discord_url: Option<String>,
+    pub players: Option<PlayerInfo>,
+}
+
+pub async fn fetch_servers() -> Result<Vec<ServerInfo>, String> {
+    let servers = request_servers().await;
+    if servers.is_err() {
+        metrics::SERVERS_FETCH_FAILURES.inc();
+    }
+    servers
+}
+
+async fn request_servers() -> Result<Vec<ServerInfo>, String> {
+    let response = reqwest::get("https://treestats.net/servers.json")
+        .await
+        .map_err(|e| format!("Failed to fetch servers: {}", e))?;
+
+    let servers: Vec<ServerInfo> = response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse servers: {}", e))?;
+
+    LAST_FETCH.store(chrono::Utc::now().timestamp(), Ordering::Relaxed);
+
+    Ok(servers)
+}
+
+/// When servers.json was last fetched successfully, as a Unix timestamp
+pub fn last_fetch() -> Option<i64> {
+    Some(LAST_FETCH.load(Ordering::Relaxed)).filter(|ts| *ts > 0)
+}
+
+/// Look up a server by exact (case-insensitive) name
+pub fn server_by_name<'a>(servers: &'a [ServerInfo], name: &str) -> Option<&'a ServerInfo> {
+    servers
+        .iter()
+        .find(|s| s.name.eq_ignore_ascii_case(name.trim()))
+}
+
+/// Result of matching a query against the server list
+pub struct ServerMatch<'a> {
+    /// The server the query resolved to, if any
+    pub server: Option<&'a ServerInfo>,
+    /// The most similar server and its similarity score, even when it wasn't
+    /// similar enough to count as a match
+    pub closest: Option<(&'a ServerInfo, f64)>,
+}
+
+/// Find a server by name
+///
+/// Aliases are consulted first, in the order given (guild aliases before
+/// global ones), then exact names, then fuzzy matching.
+pub fn find_server<'a>(
+    servers: &'a [ServerInfo],
+    aliases: &[Alias],
+    query: &str,
+) -> ServerMatch<'a> {
+    let alias_target = aliases
+        .iter()
+        .find(|a| a.alias.eq_ignore_ascii_case(query.trim()))
+        .and_then(|a| server_by_name(servers, &a.server_name));
+    if let Some(server) = alias_target {
+        return ServerMatch {
+            server: Some(server),
+            closest: Some((server, 1.0)),
+        };
+    }
+
+    // Then try exact case-insensitive match
+    if let Some(server) = server_by_name(servers, query) {
+        return ServerMatch {
+            server: Some(server),
+            closest: Some((server, 1.0)),
+        };
+    }
+
+    // Fall back to fuzzy matching with a threshold
+    // Require query to be at least 50% of the server name length to avoid very short queries matching
+    const SIMILARITY_THRESHOLD: f64 = 0.8;
+    const MIN_QUERY_LENGTH_RATIO: f64 = 0.5;
+
+    let scored: Vec<(&ServerInfo, f64)> = servers
+        .iter()
+        .map(|s| {
+            let similarity = strsim::jaro_winkler(&s.name.to_lowercase(), &query.to_lowercase());
+            (s, similarity)
+        })
+        .collect();
+
+    let server = scored
+        .iter()
+        .filter(|(s, _)| {
+            let min_length = (s.name.len() as f64 * MIN_QUERY_LENGTH_RATIO).ceil() as usize;
+            query.len() >= min_length
+        })
+        .filter(|(_, similarity)| *similarity >= SIMILARITY_THRESHOLD)
+        .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
+        .map(|(server, _)| *server);
+
+    let closest = scored
+        .into_iter()
+        .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap());
+
+    ServerMatch { server, closest }
+}
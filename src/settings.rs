@@ -0,0 +1,251 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use serenity::model::id::GuildId;
+use tokio::sync::{Mutex, RwLock};
+use tracing::error;
+
+use crate::db::{GuildSettings, SETTING_PCAP_IN_DMS};
+use crate::store::SettingsStore;
+
+/// How long cached settings are trusted before being reloaded
+const CACHE_TTL: Duration = Duration::from_secs(60);
+
+/// A cached value and when it was loaded
+#[derive(Clone)]
+struct Cached<T> {
+    value: T,
+    loaded: Instant,
+}
+
+impl<T: Clone> Cached<T> {
+    fn new(value: T) -> Self {
+        Self {
+            value,
+            loaded: Instant::now(),
+        }
+    }
+
+    fn fresh(&self) -> Option<T> {
+        (self.loaded.elapsed() < CACHE_TTL).then(|| self.value.clone())
+    }
+}
+
+/// In-memory cache of guild and bot-wide settings in front of the store
+///
+/// Changes made through this cache show up immediately. Other instances
+/// sharing the database can change settings too, so entries are reloaded
+/// once they're older than [`CACHE_TTL`].
+pub struct SettingsCache {
+    store: Arc<dyn SettingsStore>,
+    cache: RwLock<HashMap<GuildId, Cached<GuildSettings>>>,
+    pcap_in_dms: RwLock<Option<Cached<bool>>>,
+    /// Held while a guild's settings are changed, so concurrent changes
+    /// can't clobber each other
+    updating: Mutex<HashMap<GuildId, Arc<Mutex<()>>>>,
+    /// The same for the DM setting
+    updating_dms: Mutex<()>,
+}
+
+impl SettingsCache {
+    pub fn new(store: Arc<dyn SettingsStore>) -> Self {
+        Self {
+            store,
+            cache: RwLock::new(HashMap::new()),
+            pcap_in_dms: RwLock::new(None),
+            updating: Mutex::new(HashMap::new()),
+            updating_dms: Mutex::new(()),
+        }
+    }
+
+    /// Get a guild's settings, loading them on first use and when they expire
+    ///
+    /// If they can't be reloaded the expired settings are used, and DMs and
+    /// guilds whose settings were never loaded get the defaults.
+    pub async fn get(&self, guild_id: Option<GuildId>) -> GuildSettings {
+        let Some(guild_id) = guild_id else {
+            return GuildSettings::default();
+        };
+
+        if let Some(settings) = self
+            .cache
+            .read()
+            .await
+            .get(&guild_id)
+            .and_then(Cached::fresh)
+        {
+            return settings;
+        }
+
+        match self.store.get_guild_settings(&guild_id.to_string()).await {
+            Ok(settings) => {
+                self.cache
+                    .write()
+                    .await
+                    .insert(guild_id, Cached::new(settings.clone()));
+                settings
+            }
+            Err(e) => {
+                // Not refreshed, so we'll try again next time
+                error!("Failed to load settings for guild {}: {:#}", guild_id, e);
+                self.cache
+                    .read()
+                    .await
+                    .get(&guild_id)
+                    .map(|cached| cached.value.clone())
+                    .unwrap_or_default()
+            }
+        }
+    }
+
+    /// Change a guild's settings and save them, returning the new settings
+    pub async fn update(
+        &self,
+        guild_id: GuildId,
+        change: impl FnOnce(&mut GuildSettings),
+    ) -> Result<GuildSettings> {
+        // Only this guild's changes wait for each other; reads go on from the
+        // cache meanwhile
+        let lock = self
+            .updating
+            .lock()
+            .await
+            .entry(guild_id)
+            .or_default()
+            .clone();
+        let _updating = lock.lock().await;
+
+        // Start from the database in case another instance changed something
+        // since we cached it
+        let mut settings = self.store.get_guild_settings(&guild_id.to_string()).await?;
+        change(&mut settings);
+
+        self.store
+            .save_guild_settings(&guild_id.to_string(), &settings)
+            .await?;
+        self.cache
+            .write()
+            .await
+            .insert(guild_id, Cached::new(settings.clone()));
+
+        Ok(settings)
+    }
+
+    /// Whether captures sent in DMs are detected (on unless turned off)
+    pub async fn pcap_in_dms(&self) -> bool {
+        if let Some(enabled) = self
+            .pcap_in_dms
+            .read()
+            .await
+            .as_ref()
+            .and_then(Cached::fresh)
+        {
+            return enabled;
+        }
+
+        match self.store.get_bot_setting(SETTING_PCAP_IN_DMS).await {
+            Ok(value) => {
+                let enabled = value.is_none_or(|v| v == "true");
+                *self.pcap_in_dms.write().await = Some(Cached::new(enabled));
+                enabled
+            }
+            Err(e) => {
+                error!("Failed to load DM detection setting: {:#}", e);
+                self.pcap_in_dms
+                    .read()
+                    .await
+                    .as_ref()
+                    .is_none_or(|cached| cached.value)
+            }
+        }
+    }
+
+    /// Turn detection of captures sent in DMs on or off
+    pub async fn set_pcap_in_dms(&self, enabled: bool) -> Result<()> {
+        let _updating = self.updating_dms.lock().await;
+        self.store
+            .set_bot_setting(SETTING_PCAP_IN_DMS, &enabled.to_string())
+            .await?;
+        *self.pcap_in_dms.write().await = Some(Cached::new(enabled));
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serenity::async_trait;
+    use tokio::sync::Notify;
+
+    use super::*;
+    use crate::memory_store::MemoryStore;
+
+    /// Holds every guild settings save until `release` is notified
+    #[derive(Default)]
+    struct SlowStore {
+        inner: MemoryStore,
+        release: Notify,
+    }
+
+    #[async_trait]
+    impl SettingsStore for SlowStore {
+        async fn get_guild_settings(&self, guild_id: &str) -> Result<GuildSettings> {
+            self.inner.get_guild_settings(guild_id).await
+        }
+
+        async fn save_guild_settings(
+            &self,
+            guild_id: &str,
+            settings: &GuildSettings,
+        ) -> Result<()> {
+            self.release.notified().await;
+            self.inner.save_guild_settings(guild_id, settings).await
+        }
+
+        async fn get_bot_setting(&self, key: &str) -> Result<Option<String>> {
+            self.inner.get_bot_setting(key).await
+        }
+
+        async fn set_bot_setting(&self, key: &str, value: &str) -> Result<()> {
+            self.inner.set_bot_setting(key, value).await
+        }
+    }
+
+    #[tokio::test]
+    async fn reads_go_on_during_a_slow_update() {
+        let store = Arc::new(SlowStore::default());
+        let cache = Arc::new(SettingsCache::new(store.clone()));
+        let guild = GuildId::new(1);
+        cache.get(Some(guild)).await;
+
+        let updating = tokio::spawn({
+            let cache = cache.clone();
+            async move {
+                cache
+                    .update(guild, |settings| settings.auto_summarize = true)
+                    .await
+            }
+        });
+        tokio::task::yield_now().await;
+
+        // Neither the guild being changed nor any other has to wait
+        let read = async {
+            (
+                cache.get(Some(guild)).await,
+                cache.get(Some(GuildId::new(2))).await,
+            )
+        };
+        let (same, _) = tokio::time::timeout(Duration::from_secs(1), read)
+            .await
+            .expect("reads waited for the update");
+        assert!(!same.auto_summarize);
+
+        store.release.notify_one();
+        assert!(updating.await.unwrap().unwrap().auto_summarize);
+        assert!(cache.get(Some(guild)).await.auto_summarize);
+    }
+}
@@ -0,0 +1,144 @@
+use anyhow::Result;
+use serenity::async_trait;
+
+use crate::db::{
+    Alias, CommandLog, DailyUsage, Database, GuildSettings, RecentLog, ServerQuery, UnmatchedQuery,
+    UserLog, UserStats,
+};
+
+/// Where command logs are recorded and the usage stats built from them
+///
+/// Bot logic depends on this and the other store traits rather than on
+/// [`Database`], so it can be tested without a database.
+#[async_trait]
+pub trait CommandLogStore: Send + Sync {
+    /// Record a command invocation, anonymized if the user opted out
+    async fn log_command(&self, log: CommandLog) -> Result<()>;
+    /// Record several command invocations at once
+    async fn log_commands(&self, logs: Vec<CommandLog>) -> Result<()> {
+        for log in logs {
+            self.log_command(log).await?;
+        }
+        Ok(())
+    }
+    /// Successful uses per command, most used first
+    async fn get_command_stats(&self) -> Result<Vec<(String, i64)>>;
+    /// The most recent logs, newest first
+    async fn get_recent_logs(&self, limit: i64) -> Result<Vec<RecentLog>>;
+    /// Total successful uses
+    async fn get_total_uses(&self) -> Result<i64>;
+    async fn get_user_stats(&self, user_id: &str) -> Result<UserStats>;
+    /// Successful uses per day over the last `days` days, newest first
+    async fn get_usage_over_time(&self, days: i64) -> Result<Vec<DailyUsage>>;
+    /// Every log for a user, oldest first
+    async fn get_user_logs(&self, user_id: &str) -> Result<Vec<UserLog>>;
+    /// Delete a user's logs and opt them out, returning how many were deleted
+    async fn delete_user_data(&self, user_id: &str) -> Result<u64>;
+}
+
+#[async_trait]
+impl CommandLogStore for Database {
+    async fn log_command(&self, log: CommandLog) -> Result<()> {
+        Database::log_command(self, log).await
+    }
+
+    async fn log_commands(&self, logs: Vec<CommandLog>) -> Result<()> {
+        Database::log_commands(self, logs).await
+    }
+
+    async fn get_command_stats(&self) -> Result<Vec<(String, i64)>> {
+        Database::get_command_stats(self).await
+    }
+
+    async fn get_recent_logs(&self, limit: i64) -> Result<Vec<RecentLog>> {
+        Database::get_recent_logs(self, limit).await
+    }
+
+    async fn get_total_uses(&self) -> Result<i64> {
+        Database::get_total_uses(self).await
+    }
+
+    async fn get_user_stats(&self, user_id: &str) -> Result<UserStats> {
+        Database::get_user_stats(self, user_id).await
+    }
+
+    async fn get_usage_over_time(&self, days: i64) -> Result<Vec<DailyUsage>> {
+        Database::get_usage_over_time(self, days).await
+    }
+
+    async fn get_user_logs(&self, user_id: &str) -> Result<Vec<UserLog>> {
+        Database::get_user_logs(self, user_id).await
+    }
+
+    async fn delete_user_data(&self, user_id: &str) -> Result<u64> {
+        Database::delete_user_data(self, user_id).await
+    }
+}
+
+/// Server aliases and the `/server` lookups made with them
+#[async_trait]
+pub trait ServerStore: Send + Sync {
+    /// The aliases visible in a guild: its own first, then global ones
+    async fn get_aliases(&self, guild_id: Option<&str>) -> Result<Vec<Alias>>;
+    /// Add an alias, replacing any existing alias with the same name and scope
+    async fn add_alias(&self, alias: &Alias, created_by: &str) -> Result<()>;
+    /// Remove an alias, returning whether it existed
+    async fn remove_alias(&self, alias: &str, guild_id: Option<&str>) -> Result<bool>;
+    async fn log_server_query(&self, query: ServerQuery) -> Result<()>;
+    /// The most common queries that didn't resolve, with the closest match
+    async fn get_unmatched_queries(&self, limit: i64) -> Result<Vec<UnmatchedQuery>>;
+}
+
+#[async_trait]
+impl ServerStore for Database {
+    async fn get_aliases(&self, guild_id: Option<&str>) -> Result<Vec<Alias>> {
+        Database::get_aliases(self, guild_id).await
+    }
+
+    async fn add_alias(&self, alias: &Alias, created_by: &str) -> Result<()> {
+        Database::add_alias(self, alias, created_by).await
+    }
+
+    async fn remove_alias(&self, alias: &str, guild_id: Option<&str>) -> Result<bool> {
+        Database::remove_alias(self, alias, guild_id).await
+    }
+
+    async fn log_server_query(&self, query: ServerQuery) -> Result<()> {
+        Database::log_server_query(self, query).await
+    }
+
+    async fn get_unmatched_queries(&self, limit: i64) -> Result<Vec<UnmatchedQuery>> {
+        Database::get_unmatched_queries(self, limit).await
+    }
+}
+
+/// Guild and bot-wide settings, read through [`SettingsCache`]
+///
+/// [`SettingsCache`]: crate::settings::SettingsCache
+#[async_trait]
+pub trait SettingsStore: Send + Sync {
+    /// A guild's settings, or the defaults if it hasn't changed any
+    async fn get_guild_settings(&self, guild_id: &str) -> Result<GuildSettings>;
+    async fn save_guild_settings(&self, guild_id: &str, settings: &GuildSettings) -> Result<()>;
+    async fn get_bot_setting(&self, key: &str) -> Result<Option<String>>;
+    async fn set_bot_setting(&self, key: &str, value: &str) -> Result<()>;
+}
+
+#[async_trait]
+impl SettingsStore for Database {
+    async fn get_guild_settings(&self, guild_id: &str) -> Result<GuildSettings> {
+        Database::get_guild_settings(self, guild_id).await
+    }
+
+    async fn save_guild_settings(&self, guild_id: &str, settings: &GuildSettings) -> Result<()> {
+        Database::save_guild_settings(self, guild_id, settings).await
+    }
+
+    async fn get_bot_setting(&self, key: &str) -> Result<Option<String>> {
+        Database::get_bot_setting(self, key).await
+    }
+
+    async fn set_bot_setting(&self, key: &str, value: &str) -> Result<()> {
+        Database::set_bot_setting(self, key, value).await
+    }
+}
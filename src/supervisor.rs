@@ -0,0 +1,223 @@
+use std::future::Future;
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serenity::Client;
+use serenity::gateway::ShardManager;
+use tokio::sync::{Notify, watch};
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
+
+use crate::log_queue::{LogQueue, LogWriter};
+
+/// How long each half gets to stop cleanly before it's abandoned
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Why `bot serve` stopped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exit {
+    /// Asked to by SIGTERM or Ctrl+C
+    Signal,
+    /// The Discord client stopped, e.g. because the token was rejected
+    BotStopped,
+    /// The web server stopped with an error
+    WebStopped,
+}
+
+impl Exit {
+    /// 0 for a requested shutdown; 1 is left for startup errors
+    pub fn code(self) -> ExitCode {
+        match self {
+            Exit::Signal => ExitCode::SUCCESS,
+            Exit::BotStopped => ExitCode::from(3),
+            Exit::WebStopped => ExitCode::from(4),
+        }
+    }
+}
+
+/// Periodic database work running alongside the bot, like maintenance and
+/// backups
+pub struct Tasks {
+    shutdown: watch::Sender<bool>,
+    handles: Vec<JoinHandle<()>>,
+}
+
+impl Tasks {
+    pub fn new() -> Self {
+        Self {
+            shutdown: watch::Sender::new(false),
+            handles: Vec::new(),
+        }
+    }
+
+    /// Run a task in the background, handing it a receiver that changes when
+    /// it should stop
+    pub fn spawn<F>(&mut self, task: impl FnOnce(watch::Receiver<bool>) -> F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let handle = tokio::spawn(task(self.shutdown.subscribe()));
+        self.handles.push(handle);
+    }
+
+    /// Ask every task to stop and wait for them to finish what they're doing
+    async fn stop(self) {
+        self.shutdown.send_replace(true);
+        if tokio::time::timeout(
+            SHUTDOWN_TIMEOUT,
+            futures_util::future::join_all(self.handles),
+        )
+        .await
+        .is_err()
+        {
+            warn!("Background tasks didn't stop within {:?}", SHUTDOWN_TIMEOUT);
+        }
+    }
+}
+
+/// The running Discord client
+pub struct Bot {
+    shard_manager: Arc<ShardManager>,
+    handle: JoinHandle<Result<(), serenity::Error>>,
+    logs: Arc<LogQueue>,
+    log_writer: LogWriter,
+    tasks: Tasks,
+}
+
+impl Bot {
+    /// Connect `client` to the gateway in the background
+    pub fn start(
+        mut client: Client,
+        logs: Arc<LogQueue>,
+        log_writer: LogWriter,
+        tasks: Tasks,
+    ) -> Self {
+        Self {
+            shard_manager: client.shard_manager.clone(),
+            handle: tokio::spawn(async move { client.start().await }),
+            logs,
+            log_writer,
+            tasks,
+        }
+    }
+
+    /// Disconnect from the gateway, unless the client already stopped, write
+    /// out queued command logs and let background tasks finish
+    async fn stop(self, running: bool) {
+        if running {
+            self.shard_manager.shutdown_all().await;
+            if tokio::time::timeout(SHUTDOWN_TIMEOUT, self.handle)
+                .await
+                .is_err()
+            {
+                warn!("Discord client didn't stop within {:?}", SHUTDOWN_TIMEOUT);
+            }
+        }
+
+        let flush = async {
+            if tokio::time::timeout(SHUTDOWN_TIMEOUT, self.log_writer.flush())
+                .await
+                .is_err()
+            {
+                warn!("Command logs weren't written within {:?}", SHUTDOWN_TIMEOUT);
+            }
+        };
+        tokio::join!(flush, self.tasks.stop());
+        if self.logs.dropped() > 0 {
+            info!(
+                "Dropped {} command log(s) because the queue was full or they couldn't be written",
+                self.logs.dropped()
+            );
+        }
+    }
+}
+
+/// The running web server
+pub struct Web {
+    shutdown: Arc<Notify>,
+    handle: JoinHandle<std::io::Result<()>>,
+}
+
+impl Web {
+    /// Serve `app` on `listener` in the background
+    pub fn start(listener: tokio::net::TcpListener, app: axum::Router) -> Self {
+        let shutdown = Arc::new(Notify::new());
+        let stopped = shutdown.clone();
+        let handle = tokio::spawn(async move {
+            axum::serve(listener, app)
+                .with_graceful_shutdown(async move { stopped.notified().await })
+                .await
+        });
+
+        Self { shutdown, handle }
+    }
+
+    /// Stop accepting connections and wait for open requests to finish,
+    /// unless the server already stopped
+    async fn stop(self, running: bool) {
+        if !running {
+            return;
+        }
+
+        // Stores a permit, so this works even if the server isn't waiting yet
+        self.shutdown.notify_one();
+        if tokio::time::timeout(SHUTDOWN_TIMEOUT, self.handle)
+            .await
+            .is_err()
+        {
+            warn!("Web server didn't stop within {:?}", SHUTDOWN_TIMEOUT);
+        }
+    }
+}
+
+/// Wait for a task if there is one, otherwise forever
+async fn finished<T>(handle: Option<&mut JoinHandle<T>>) -> Result<T, tokio::task::JoinError> {
+    match handle {
+        Some(handle) => handle.await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Run until `signal` fires or either half stops, then shut both down
+///
+/// Neither half is restarted: the Discord client already reconnects after
+/// network errors, so when it stops for good the process exits and whatever
+/// runs it decides whether to restart.
+pub async fn supervise(
+    mut bot: Option<Bot>,
+    mut web: Option<Web>,
+    signal: impl Future<Output = ()>,
+) -> Exit {
+    let exit = tokio::select! {
+        _ = signal => Exit::Signal,
+        result = finished(bot.as_mut().map(|bot| &mut bot.handle)) => {
+            match result {
+                Ok(Ok(())) => error!("Discord client stopped unexpectedly"),
+                Ok(Err(e)) => error!("Discord client failed: {}", e),
+                Err(e) => error!("Discord client panicked: {}", e),
+            }
+            Exit::BotStopped
+        }
+        result = finished(web.as_mut().map(|web| &mut web.handle)) => {
+            match result {
+                Ok(Ok(())) => error!("Web server stopped unexpectedly"),
+                Ok(Err(e)) => error!("Web server failed: {}", e),
+                Err(e) => error!("Web server panicked: {}", e),
+            }
+            Exit::WebStopped
+        }
+    };
+
+    info!("Shutting down ({:?})", exit);
+
+    // Stop taking requests first, then finish the bot's work
+    if let Some(web) = web {
+        web.stop(exit != Exit::WebStopped).await;
+    }
+    if let Some(bot) = bot {
+        bot.stop(exit != Exit::BotStopped).await;
+    }
+
+    exit
+}
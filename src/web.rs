@@ -1,16 +1,44 @@
+use std::time::Instant;
+
 use axum::{
     Json, Router,
-    extract::{Path, Request},
+    body::Body,
+    extract::{MatchedPath, Path, Query, Request, State},
     middleware::{self, Next},
-    response::Response,
+    response::{IntoResponse, Response},
     routing::get,
 };
-use http::{Method, StatusCode};
-use log::info;
+use http::{HeaderMap, HeaderValue, Method, StatusCode, header};
+use log::{debug, info};
 use serde::{Deserialize, Serialize};
 use tower_http::{services::ServeDir, trace::TraceLayer};
 
-use crate::discord::{download_attachment, fetch_message};
+use crate::discord::{download_attachment, fetch_message, is_pcap_file, is_valid_snowflake};
+use crate::health::{Health, Readiness};
+use crate::protocol::{self, DecodedPacket, Direction, PacketFilter};
+use crate::scrub;
+use crate::{metrics, pcap};
+
+/// Default and maximum number of packets returned per page
+const DEFAULT_PACKET_LIMIT: usize = 1000;
+const MAX_PACKET_LIMIT: usize = 10_000;
+
+/// Where the web server gets message attachments from
+#[derive(Clone)]
+pub enum CaptureSource {
+    /// Discord's API, using the bot token
+    Discord { token: String },
+    /// Files in `<dir>/<channel_id>/<message_id>/`, for running without
+    /// Discord
+    Fixtures { dir: std::path::PathBuf },
+}
+
+/// Shared by every request handler
+#[derive(Clone)]
+struct AppState {
+    source: CaptureSource,
+    health: Health,
+}
 
 #[derive(Deserialize)]
 struct DiscordParams {
@@ -23,40 +51,130 @@ struct DiscordError {
     error: String,
 }
 
+#[derive(Deserialize)]
+struct PacketQuery {
+    /// Index of the first matching packet to return
+    #[serde(default)]
+    start: usize,
+    limit: Option<usize>,
+    /// Only include packets carrying this message opcode (hex or decimal)
+    opcode: Option<String>,
+    direction: Option<Direction>,
+    /// Time window in microseconds since the Unix epoch
+    from_us: Option<i64>,
+    to_us: Option<i64>,
+    /// `json` (default) or `ndjson`
+    format: Option<String>,
+}
+
+impl PacketQuery {
+    fn filter(&self) -> Result<PacketFilter, (StatusCode, Json<DiscordError>)> {
+        let opcode = match self.opcode.as_deref() {
+            Some(value) => Some(protocol::parse_opcode(value).ok_or_else(|| {
+                (
+                    StatusCode::BAD_REQUEST,
+                    Json(DiscordError {
+                        error: format!("Invalid opcode: {value}"),
+                    }),
+                )
+            })?),
+            None => None,
+        };
+
+        Ok(PacketFilter {
+            opcode,
+            direction: self.direction,
+            from_us: self.from_us,
+            to_us: self.to_us,
+        })
+    }
+}
+
+#[derive(Serialize)]
+struct PacketPage {
+    /// Number of packets matching the filter
+    total: usize,
+    start: usize,
+    packets: Vec<DecodedPacket>,
+}
+
 async fn log_requests(req: Request<axum::body::Body>, next: Next) -> Response {
     let method = req.method().clone();
     let uri = req.uri().clone();
     let res = next.run(req).await;
     let status = res.status();
-    println!(">>> {method} {uri} {status}");
+    debug!("{method} {uri} {status}");
 
     res
 }
 
-async fn discord_pull(
-    Path(params): Path<DiscordParams>,
-) -> Result<Vec<u8>, (StatusCode, Json<DiscordError>)> {
-    println!(
-        "==> Discord pull request: channel={}, msg={}",
-        params.channel_id, params.message_id
-    );
-    info!(
-        "Discord pull request: channel={}, msg={}",
-        params.channel_id, params.message_id
-    );
+/// Record how long each request took, by route pattern rather than path so
+/// IDs don't become labels
+async fn track_metrics(req: Request<axum::body::Body>, next: Next) -> Response {
+    let method = req.method().clone();
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or("static", |path| path.as_str())
+        .to_string();
+    let started = Instant::now();
 
-    // Check if token is available
-    let token = std::env::var("DISCORD_BOT_TOKEN").map_err(|_| {
-        (
-            StatusCode::UNAUTHORIZED,
-            Json(DiscordError {
-                error: "Discord OAuth token not configured".to_string(),
-            }),
-        )
-    })?;
+    let res = next.run(req).await;
+
+    metrics::HTTP_REQUEST_DURATION
+        .with_label_values(&[method.as_str(), route.as_str(), res.status().as_str()])
+        .observe(started.elapsed().as_secs_f64());
+
+    res
+}
+
+/// A capture file pulled from a Discord message
+struct CaptureFile {
+    filename: String,
+    data: Vec<u8>,
+}
+
+impl CaptureFile {
+    /// Filename without its extension, for naming derived files
+    fn stem(&self) -> &str {
+        self.filename
+            .rsplit_once('.')
+            .map_or(self.filename.as_str(), |(stem, _)| stem)
+    }
+
+    fn content_type(&self) -> &'static str {
+        if self.filename.to_lowercase().ends_with(".pcapng") {
+            "application/x-pcapng"
+        } else {
+            "application/vnd.tcpdump.pcap"
+        }
+    }
+}
 
+/// Content-Disposition value for downloading a file as `filename`
+fn content_disposition(filename: &str) -> String {
+    // Keep the header value plain ASCII and unambiguous
+    let filename: String = filename
+        .chars()
+        .map(|c| {
+            if (c.is_ascii_graphic() || c == ' ') && c != '"' && c != '\\' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+
+    format!("attachment; filename=\"{filename}\"")
+}
+
+/// Fetch a message from Discord and download its first PCAP attachment
+async fn download_capture(
+    token: &str,
+    params: &DiscordParams,
+) -> Result<(String, Vec<u8>), (StatusCode, Json<DiscordError>)> {
     // Fetch message from Discord API
-    let message = fetch_message(&params.channel_id, &params.message_id, &token)
+    let message = fetch_message(&params.channel_id, &params.message_id, token)
         .await
         .map_err(|(status, error)| (status, Json(DiscordError { error })))?;
 
@@ -64,7 +182,7 @@ async fn discord_pull(
     let pcap_attachment = message
         .attachments
         .iter()
-        .find(|a| a.filename.ends_with(".pcap") || a.filename.ends_with(".pcapng"))
+        .find(|a| is_pcap_file(&a.filename))
         .ok_or_else(|| {
             (
                 StatusCode::BAD_REQUEST,
@@ -85,7 +203,322 @@ async fn discord_pull(
         pcap_data.len()
     );
 
-    Ok(pcap_data)
+    Ok((pcap_attachment.filename.clone(), pcap_data))
+}
+
+/// Read the first PCAP file (by name) in `<dir>/<channel_id>/<message_id>/`
+async fn read_fixture(
+    dir: &std::path::Path,
+    params: &DiscordParams,
+) -> Result<(String, Vec<u8>), (StatusCode, Json<DiscordError>)> {
+    let error = |status: StatusCode, error: String| (status, Json(DiscordError { error }));
+
+    // Same IDs as Discord accepts, which also keeps requests inside `dir`
+    if !is_valid_snowflake(&params.channel_id) {
+        return Err(error(
+            StatusCode::BAD_REQUEST,
+            "Invalid channel ID format".to_string(),
+        ));
+    }
+    if !is_valid_snowflake(&params.message_id) {
+        return Err(error(
+            StatusCode::BAD_REQUEST,
+            "Invalid message ID format".to_string(),
+        ));
+    }
+
+    let message_dir = dir.join(&params.channel_id).join(&params.message_id);
+    let mut entries = tokio::fs::read_dir(&message_dir)
+        .await
+        .map_err(|_| error(StatusCode::NOT_FOUND, "Message not found".to_string()))?;
+
+    let mut filenames = Vec::new();
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let filename = entry.file_name().to_string_lossy().to_string();
+        if is_pcap_file(&filename) {
+            filenames.push(filename);
+        }
+    }
+    filenames.sort();
+
+    let filename = filenames.into_iter().next().ok_or_else(|| {
+        error(
+            StatusCode::BAD_REQUEST,
+            "No PCAP attachments found in message".to_string(),
+        )
+    })?;
+    let data = tokio::fs::read(message_dir.join(&filename))
+        .await
+        .map_err(|e| {
+            error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to read fixture: {e}"),
+            )
+        })?;
+
+    info!("Read PCAP fixture: {} ({} bytes)", filename, data.len());
+
+    Ok((filename, data))
+}
+
+/// Get a message's first PCAP attachment from the configured source
+///
+/// Captures are always scrubbed before they leave this function so that no
+/// endpoint can expose players' addresses or login details.
+async fn fetch_capture(
+    state: &AppState,
+    params: &DiscordParams,
+) -> Result<CaptureFile, (StatusCode, Json<DiscordError>)> {
+    let (filename, pcap_data) = match &state.source {
+        CaptureSource::Discord { token } => download_capture(token, params).await?,
+        CaptureSource::Fixtures { dir } => read_fixture(dir, params).await?,
+    };
+
+    let (pcap_data, report) = scrub::scrub(&pcap_data).map_err(|e| {
+        (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(DiscordError {
+                error: format!("Failed to parse capture: {e}"),
+            }),
+        )
+    })?;
+
+    info!(
+        "Scrubbed {}: {} packets, {} addresses, {} logins",
+        filename, report.packets, report.addresses, report.logins
+    );
+
+    Ok(CaptureFile {
+        filename,
+        data: pcap_data,
+    })
+}
+
+/// Resolve a `Range` header against a body of `len` bytes
+///
+/// Returns `None` when the header should be ignored and the full body served,
+/// `Some(Err(()))` when the range can't be satisfied, and otherwise the
+/// inclusive byte range to serve. Only single ranges are supported.
+fn parse_range(value: &str, len: u64) -> Option<Result<(u64, u64), ()>> {
+    let spec = value.trim().strip_prefix("bytes=")?;
+
+    if spec.contains(',') {
+        return None;
+    }
+
+    let (start, end) = spec.split_once('-')?;
+    let (start, end) = (start.trim(), end.trim());
+
+    let range = if start.is_empty() {
+        // Suffix range: the last N bytes
+        let suffix: u64 = end.parse().ok()?;
+        if suffix == 0 || len == 0 {
+            return Some(Err(()));
+        }
+        (len.saturating_sub(suffix), len - 1)
+    } else {
+        let start: u64 = start.parse().ok()?;
+        let end: u64 = if end.is_empty() {
+            len.saturating_sub(1)
+        } else {
+            end.parse::<u64>().ok()?.min(len.saturating_sub(1))
+        };
+        if start >= len || start > end {
+            return Some(Err(()));
+        }
+        (start, end)
+    };
+
+    Some(Ok(range))
+}
+
+/// Build a download response for a capture, honoring `Range` requests
+fn capture_response(capture: CaptureFile, request_headers: &HeaderMap) -> Response {
+    let len = capture.data.len() as u64;
+    let range = request_headers
+        .get(header::RANGE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| parse_range(v, len));
+
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static(capture.content_type()),
+    );
+    if let Ok(value) = HeaderValue::from_str(&content_disposition(&capture.filename)) {
+        headers.insert(header::CONTENT_DISPOSITION, value);
+    }
+    headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+
+    match range {
+        Some(Ok((start, end))) => {
+            let body = capture.data[start as usize..=end as usize].to_vec();
+            headers.insert(header::CONTENT_LENGTH, HeaderValue::from(body.len()));
+            if let Ok(value) = HeaderValue::from_str(&format!("bytes {start}-{end}/{len}")) {
+                headers.insert(header::CONTENT_RANGE, value);
+            }
+            (StatusCode::PARTIAL_CONTENT, headers, Body::from(body)).into_response()
+        }
+        Some(Err(())) => {
+            if let Ok(value) = HeaderValue::from_str(&format!("bytes */{len}")) {
+                headers.insert(header::CONTENT_RANGE, value);
+            }
+            (StatusCode::RANGE_NOT_SATISFIABLE, headers).into_response()
+        }
+        None => {
+            headers.insert(header::CONTENT_LENGTH, HeaderValue::from(len));
+            (StatusCode::OK, headers, Body::from(capture.data)).into_response()
+        }
+    }
+}
+
+async fn discord_pull(
+    State(state): State<AppState>,
+    Path(params): Path<DiscordParams>,
+    request_headers: HeaderMap,
+) -> Result<Response, (StatusCode, Json<DiscordError>)> {
+    info!(
+        "Discord pull request: channel={}, msg={}",
+        params.channel_id, params.message_id
+    );
+
+    let capture = fetch_capture(&state, &params).await?;
+
+    Ok(capture_response(capture, &request_headers))
+}
+
+fn parse_capture(
+    file: &CaptureFile,
+) -> Result<pcap::Capture<'_>, (StatusCode, Json<DiscordError>)> {
+    pcap::parse(&file.data).map_err(|e| {
+        (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(DiscordError {
+                error: format!("Failed to parse capture: {e}"),
+            }),
+        )
+    })
+}
+
+async fn discord_packets(
+    State(state): State<AppState>,
+    Path(params): Path<DiscordParams>,
+    Query(query): Query<PacketQuery>,
+) -> Result<Response, (StatusCode, Json<DiscordError>)> {
+    info!(
+        "Discord packets request: channel={}, msg={}",
+        params.channel_id, params.message_id
+    );
+
+    let filter = query.filter()?;
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_PACKET_LIMIT)
+        .min(MAX_PACKET_LIMIT);
+
+    let file = fetch_capture(&state, &params).await?;
+    let capture = parse_capture(&file)?;
+
+    let matching: Vec<DecodedPacket> = protocol::decode(&capture)
+        .into_iter()
+        .filter(|p| filter.matches(p))
+        .collect();
+    let total = matching.len();
+    let packets: Vec<DecodedPacket> = matching.into_iter().skip(query.start).take(limit).collect();
+
+    if query.format.as_deref() == Some("ndjson") {
+        let lines = futures_util::stream::iter(packets.into_iter().map(|packet| {
+            serde_json::to_string(&packet).map(|mut line| {
+                line.push('\n');
+                line
+            })
+        }));
+
+        return Ok((
+            [
+                (header::CONTENT_TYPE, "application/x-ndjson".to_string()),
+                (
+                    header::HeaderName::from_static("x-total-count"),
+                    total.to_string(),
+                ),
+            ],
+            Body::from_stream(lines),
+        )
+            .into_response());
+    }
+
+    Ok(Json(PacketPage {
+        total,
+        start: query.start,
+        packets,
+    })
+    .into_response())
+}
+
+async fn discord_export_csv(
+    State(state): State<AppState>,
+    Path(params): Path<DiscordParams>,
+    Query(query): Query<PacketQuery>,
+) -> Result<Response, (StatusCode, Json<DiscordError>)> {
+    info!(
+        "Discord CSV export request: channel={}, msg={}",
+        params.channel_id, params.message_id
+    );
+
+    let filter = query.filter()?;
+    let file = fetch_capture(&state, &params).await?;
+    let capture = parse_capture(&file)?;
+
+    let packets = protocol::decode(&capture);
+    let csv = protocol::to_csv(packets.iter().filter(|p| filter.matches(p)));
+
+    let filename = format!("{}.csv", file.stem());
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+            (header::CONTENT_DISPOSITION, content_disposition(&filename)),
+        ],
+        csv,
+    )
+        .into_response())
+}
+
+async fn discord_export_pcap(
+    State(state): State<AppState>,
+    Path(params): Path<DiscordParams>,
+    Query(query): Query<PacketQuery>,
+    request_headers: HeaderMap,
+) -> Result<Response, (StatusCode, Json<DiscordError>)> {
+    info!(
+        "Discord pcap export request: channel={}, msg={}",
+        params.channel_id, params.message_id
+    );
+
+    let filter = query.filter()?;
+    let file = fetch_capture(&state, &params).await?;
+    let capture = parse_capture(&file)?;
+
+    let selected = protocol::decode(&capture)
+        .into_iter()
+        .filter(|p| filter.matches(p))
+        .map(|p| &capture.packets[p.index]);
+
+    let data = pcap::write(selected).map_err(|e| {
+        (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(DiscordError {
+                error: format!("Failed to write capture: {e}"),
+            }),
+        )
+    })?;
+
+    let export = CaptureFile {
+        filename: format!("{}-filtered.pcap", file.stem()),
+        data,
+    };
+
+    Ok(capture_response(export, &request_headers))
 }
 
 async fn health() -> &'static str {
@@ -93,7 +526,32 @@ async fn health() -> &'static str {
     "OK"
 }
 
-pub fn create_router() -> Router {
+/// Metrics in Prometheus' text format
+async fn metrics_handler(State(state): State<AppState>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        metrics::render(state.health.shard_manager.as_deref()).await,
+    )
+}
+
+/// The process is up and serving requests
+async fn health_live() -> Json<serde_json::Value> {
+    Json(serde_json::json!({ "status": "ok" }))
+}
+
+/// Everything that's running can do its job; 503 otherwise
+async fn health_ready(State(state): State<AppState>) -> (StatusCode, Json<Readiness>) {
+    let readiness = state.health.readiness().await;
+    let status = if readiness.ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (status, Json(readiness))
+}
+
+pub fn create_router(source: CaptureSource, checks: Health) -> Router {
     let dist_path = std::path::PathBuf::from("dist");
     use tower_http::cors::{Any, CorsLayer};
 
@@ -106,12 +564,75 @@ pub fn create_router() -> Router {
 
     Router::new()
         .route("/api/health", get(health))
+        .route("/api/health/live", get(health_live))
+        .route("/api/health/ready", get(health_ready))
+        .route("/metrics", get(metrics_handler))
         .route(
             "/api/discord/channels/{channel_id}/messages/{message_id}/attachments",
             get(discord_pull),
         )
+        .route(
+            "/api/discord/channels/{channel_id}/messages/{message_id}/packets",
+            get(discord_packets),
+        )
+        .route(
+            "/api/discord/channels/{channel_id}/messages/{message_id}/export/csv",
+            get(discord_export_csv),
+        )
+        .route(
+            "/api/discord/channels/{channel_id}/messages/{message_id}/export/pcap",
+            get(discord_export_pcap),
+        )
         .fallback_service(ServeDir::new(&dist_path))
         .layer(cors)
         .layer(TraceLayer::new_for_http())
         .layer(middleware::from_fn(log_requests))
+        .layer(middleware::from_fn(track_metrics))
+        .with_state(AppState {
+            source,
+            health: checks,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_range_bounded() {
+        assert_eq!(parse_range("bytes=0-99", 1000), Some(Ok((0, 99))));
+        assert_eq!(parse_range("bytes=500-999", 1000), Some(Ok((500, 999))));
+        assert_eq!(parse_range(" bytes=10 - 20 ", 1000), Some(Ok((10, 20))));
+    }
+
+    #[test]
+    fn parse_range_clamps_end() {
+        assert_eq!(parse_range("bytes=900-5000", 1000), Some(Ok((900, 999))));
+        assert_eq!(parse_range("bytes=900-", 1000), Some(Ok((900, 999))));
+    }
+
+    #[test]
+    fn parse_range_suffix() {
+        assert_eq!(parse_range("bytes=-100", 1000), Some(Ok((900, 999))));
+        assert_eq!(parse_range("bytes=-5000", 1000), Some(Ok((0, 999))));
+    }
+
+    #[test]
+    fn parse_range_unsatisfiable() {
+        assert_eq!(parse_range("bytes=1000-", 1000), Some(Err(())));
+        assert_eq!(parse_range("bytes=20-10", 1000), Some(Err(())));
+        assert_eq!(parse_range("bytes=-0", 1000), Some(Err(())));
+        assert_eq!(parse_range("bytes=-10", 0), Some(Err(())));
+        assert_eq!(parse_range("bytes=0-", 0), Some(Err(())));
+    }
+
+    #[test]
+    fn parse_range_ignored() {
+        // Not a byte range we serve, so the whole file is sent instead
+        assert_eq!(parse_range("items=0-10", 1000), None);
+        assert_eq!(parse_range("bytes=0-10,20-30", 1000), None);
+        assert_eq!(parse_range("bytes=abc-10", 1000), None);
+        assert_eq!(parse_range("bytes=10", 1000), None);
+        assert_eq!(parse_range("bytes=-", 1000), None);
+    }
 }